@@ -0,0 +1,118 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Query, State},
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::DataSource;
+
+/// Request header carrying the ID a request is logged under. One is made up for requests
+/// without it, and it is echoed in the response either way.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Where to write one JSON object per request handled, for shipping to a log pipeline.
+#[derive(Clone)]
+pub struct AccessLog {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    next_request_id: Arc<AtomicU64>,
+}
+
+/// One line of the access log. Its fields are a stable schema; add to them, but don't change them.
+#[derive(Debug, Serialize)]
+struct AccessLogEntry<'a> {
+    timestamp: String,
+    request_id: &'a str,
+    method: &'a str,
+    path: &'a str,
+    series_id: Option<String>,
+    status: u16,
+    /// Size of the response body before compression, if known up front
+    bytes: Option<u64>,
+    duration_ms: f64,
+    /// Where `/v0/observations` got the observations, e.g. `cache` or `upstream`
+    cache: Option<&'static str>,
+}
+
+#[derive(Deserialize)]
+struct SeriesIdQuery {
+    series_id: Option<String>,
+}
+
+impl AccessLog {
+    /// Append to the file at `path`, or write to stdout if `path` is `-`.
+    pub fn open(path: &std::path::Path) -> std::io::Result<Self> {
+        let writer: Box<dyn Write + Send> = if path.as_os_str() == "-" {
+            Box::new(std::io::stdout())
+        } else {
+            Box::new(std::io::LineWriter::new(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?,
+            ))
+        };
+        Ok(AccessLog {
+            writer: Arc::new(Mutex::new(writer)),
+            next_request_id: Default::default(),
+        })
+    }
+
+    fn write(&self, entry: &AccessLogEntry) {
+        let Ok(mut line) = serde_json::to_vec(entry) else {
+            return;
+        };
+        line.push(b'\n');
+        // a broken log must not fail the request it describes
+        let _ = self.writer.lock().unwrap().write_all(&line);
+    }
+}
+
+/// Middleware writing an [`AccessLogEntry`] once each response is ready.
+pub async fn record(
+    State(access_log): State<AccessLog>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let started = Instant::now();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            let n = access_log.next_request_id.fetch_add(1, Ordering::Relaxed);
+            format!("{}-{n}", std::process::id())
+        });
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let series_id = Query::<SeriesIdQuery>::try_from_uri(&uri)
+        .ok()
+        .and_then(|Query(query)| query.series_id);
+    let mut response = next.run(request).await;
+    access_log.write(&AccessLogEntry {
+        timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        request_id: &request_id,
+        method: method.as_str(),
+        path: uri.path(),
+        series_id,
+        status: response.status().as_u16(),
+        bytes: response.body().size_hint().exact(),
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        cache: response
+            .extensions()
+            .get::<DataSource>()
+            .map(|source| source.as_str()),
+    });
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
@@ -0,0 +1,159 @@
+use chrono::NaiveDate;
+
+use stlouisfed_fred_web_proxy::{
+    entities::FredTransformation,
+    fred::{
+        is_transient, is_well_formed_series_id, request_observations_from_fred, FredApiError,
+        FredClient, FredObservations,
+    },
+    local_cache::RealtimeObservationsDatabase,
+};
+
+/// Populate the local cache with observations of some series, then exit.
+#[derive(clap::Args)]
+pub struct BackfillArgs {
+    /// FRED series IDs to fetch, e.g. SP500 GDP
+    #[arg(required = true)]
+    series_ids: Vec<String>,
+
+    /// Earliest observation date to fetch (YYYY-MM-DD). Defaults to the start of each series.
+    #[arg(long)]
+    observation_start: Option<NaiveDate>,
+
+    /// Latest observation date to fetch (YYYY-MM-DD). Defaults to the latest observation.
+    #[arg(long)]
+    observation_end: Option<NaiveDate>,
+
+    /// Maximum number of series fetched per minute, to stay under FRED's rate limit
+    #[arg(long, default_value_t = 60)]
+    series_per_minute: u32,
+
+    /// How many times to retry a series after a transient FRED failure
+    #[arg(long, default_value_t = 3)]
+    max_retries: u32,
+}
+
+impl BackfillArgs {
+    /// Problems with the arguments, if any
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = self
+            .series_ids
+            .iter()
+            .filter(|series_id| !is_well_formed_series_id(series_id))
+            .map(|series_id| format!("{series_id:?} is not a valid FRED series ID"))
+            .collect();
+        if let (Some(start), Some(end)) = (self.observation_start, self.observation_end) {
+            if start > end {
+                problems
+                    .push("--observation-start must not be after --observation-end".to_string());
+            }
+        }
+        if self.series_per_minute == 0 {
+            problems.push("--series-per-minute must be greater than 0".to_string());
+        }
+        problems
+    }
+}
+
+pub async fn run(
+    fred: &FredClient,
+    db: &RealtimeObservationsDatabase,
+    args: BackfillArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Backfill retries by itself, so that it can count the retries. Retrying in the client too
+    // would multiply the attempts against FRED's rate limit, and hide them from the count.
+    let fred = &fred.clone().with_retries(0, std::time::Duration::ZERO);
+    let pace = std::time::Duration::from_secs(60) / args.series_per_minute.max(1);
+    let mut failures = 0usize;
+    let mut retried = RetryStats::default();
+    for (i, series_id) in args.series_ids.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(pace).await;
+        }
+        let (result, retries) = fetch_with_retries(fred, series_id, &args).await;
+        if retries > 0 {
+            // retries are an early sign of FRED degrading, before requests fail outright
+            tracing::warn!(
+                series_id,
+                retries,
+                "backfill {} after retrying",
+                if result.is_ok() {
+                    "succeeded"
+                } else {
+                    "failed"
+                }
+            );
+            retried.series += 1;
+            retried.retries += retries;
+        }
+        match result {
+            Ok(FredObservations { observations, .. }) => {
+                db.put_observations(series_id, &observations).await?;
+                // FRED's `count` counts every row of a date with several, but one is kept
+                if !observations.is_empty() {
+                    db.put_observation_count(
+                        series_id,
+                        args.observation_start,
+                        args.observation_end,
+                        observations.len(),
+                    )
+                    .await?;
+                }
+                println!("{series_id}: {} rows written", observations.len());
+            }
+            Err(e) => {
+                failures += 1;
+                println!("{series_id}: failed: {e}");
+            }
+        }
+    }
+    if retried.series > 0 {
+        tracing::warn!(
+            "{} of {} series needed retries ({} retries in all)",
+            retried.series,
+            args.series_ids.len(),
+            retried.retries
+        );
+    }
+    if failures > 0 {
+        return Err(format!("{failures} of {} series failed", args.series_ids.len()).into());
+    }
+    Ok(())
+}
+
+/// How much retrying a backfill took
+#[derive(Default)]
+struct RetryStats {
+    /// Series that needed at least one retry
+    series: usize,
+    retries: u32,
+}
+
+/// Fetch the observations of a series, retrying transient failures. Also returns how many
+/// retries it took.
+async fn fetch_with_retries(
+    fred: &FredClient,
+    series_id: &str,
+    args: &BackfillArgs,
+) -> (Result<FredObservations, FredApiError>, u32) {
+    let mut attempt = 0;
+    loop {
+        let result = request_observations_from_fred(
+            fred,
+            series_id,
+            args.observation_start,
+            args.observation_end,
+            None,
+            None,
+            FredTransformation::default(),
+        )
+        .await;
+        match result {
+            Err(e) if attempt < args.max_retries && is_transient(&e) => {
+                attempt += 1;
+                tokio::time::sleep(std::time::Duration::from_secs(1 << attempt)).await;
+            }
+            result => return (result, attempt),
+        }
+    }
+}
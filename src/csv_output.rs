@@ -0,0 +1,90 @@
+use crate::entities::{DateFormat, RealtimeObservation};
+
+/// Render observations as CSV with a `date,value` header line and dates in `date_format`.
+/// Missing values (FRED's `.`) are left empty, so that spreadsheets and `read_csv` see blanks
+/// rather than text, and values containing commas, quotes, or line breaks are quoted.
+///
+/// With `excel`, the output starts with a UTF-8 byte order mark and uses CRLF line endings,
+/// which is what Excel needs to detect the encoding and split rows correctly.
+pub fn observations_to_csv(
+    observations: &[RealtimeObservation],
+    date_format: DateFormat,
+    excel: bool,
+) -> String {
+    let line_ending = if excel { "\r\n" } else { "\n" };
+    let mut csv = String::with_capacity(16 + observations.len() * 20);
+    if excel {
+        csv.push('\u{feff}');
+    }
+    csv.push_str("date,value");
+    csv.push_str(line_ending);
+    for observation in observations {
+        csv.push_str(&date_format.format(observation.date));
+        csv.push(',');
+        push_field(&mut csv, &observation.value);
+        csv.push_str(line_ending);
+    }
+    csv
+}
+
+/// Append a value as a CSV field, per RFC 4180.
+fn push_field(csv: &mut String, value: &str) {
+    if value == "." {
+        return;
+    }
+    if value.contains([',', '"', '\r', '\n']) {
+        csv.push('"');
+        csv.push_str(&value.replace('"', "\"\""));
+        csv.push('"');
+    } else {
+        csv.push_str(value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::observations_to_csv;
+    use crate::entities::{DateFormat, RealtimeObservation};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_excel_csv_has_bom_and_crlf() {
+        let observations = vec![RealtimeObservation {
+            date: NaiveDate::from_ymd_opt(2023, 9, 14).unwrap(),
+            value: "4505.1".to_string(),
+            ..Default::default()
+        }];
+        assert_eq!(
+            observations_to_csv(&observations, DateFormat::Date, false),
+            "date,value\n2023-09-14,4505.1\n"
+        );
+        assert_eq!(
+            observations_to_csv(&observations, DateFormat::Date, true),
+            "\u{feff}date,value\r\n2023-09-14,4505.1\r\n"
+        );
+        assert_eq!(
+            observations_to_csv(&observations, DateFormat::Iso8601, false),
+            "date,value\n2023-09-14T00:00:00Z,4505.1\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_missing_and_quoted_values() {
+        let observation = |date: &str, value: &str| RealtimeObservation {
+            date: date.parse().unwrap(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        let observations = vec![
+            observation("2023-09-14", "4505.1"),
+            observation("2023-09-15", "."),
+            observation("2023-09-18", "1,234.5"),
+            observation("2023-09-19", "a \"quoted\" value"),
+        ];
+        let csv = observations_to_csv(&observations, DateFormat::Date, false);
+        assert_eq!(
+            csv,
+            "date,value\n2023-09-14,4505.1\n2023-09-15,\n2023-09-18,\"1,234.5\"\n2023-09-19,\"a \"\"quoted\"\" value\"\n"
+        );
+    }
+}
@@ -2,7 +2,8 @@ const YYYY_MM_DD_FORMAT: &str = "%Y-%m-%d";
 
 pub mod yyyy_mm_dd {
     use chrono::NaiveDate;
-    use serde::{self, Deserialize, Deserializer, Serializer};
+    use core::fmt;
+    use serde::{self, de, Deserialize, Deserializer, Serializer};
 
     pub fn serialize<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -12,12 +13,54 @@ pub mod yyyy_mm_dd {
         serializer.serialize_str(&s)
     }
 
+    /// Parses the date straight from the input without allocating a `String` for it, as
+    /// there are several dates per row of a large FRED response.
     pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        NaiveDate::parse_from_str(&s, super::YYYY_MM_DD_FORMAT).map_err(serde::de::Error::custom)
+        deserializer.deserialize_str(DateVisitor)
+    }
+
+    struct DateVisitor;
+
+    impl<'de> de::Visitor<'de> for DateVisitor {
+        type Value = NaiveDate;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a date like 2023-09-19")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<NaiveDate, E>
+        where
+            E: de::Error,
+        {
+            NaiveDate::parse_from_str(value, super::YYYY_MM_DD_FORMAT).map_err(E::custom)
+        }
+    }
+
+    /// Serialize an array of dates, for use with `#[serde(serialize_with = ...)]`.
+    pub fn serialize_vec<S>(dates: &[NaiveDate], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(
+            dates
+                .iter()
+                .map(|date| date.format(super::YYYY_MM_DD_FORMAT).to_string()),
+        )
+    }
+
+    /// Deserialize an array of dates, for use with `#[serde(deserialize_with = ...)]`.
+    pub fn deserialize_vec<'de, D>(deserializer: D) -> Result<Vec<NaiveDate>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| NaiveDate::parse_from_str(s, super::YYYY_MM_DD_FORMAT))
+            .collect::<Result<_, _>>()
+            .map_err(serde::de::Error::custom)
     }
 }
 
@@ -57,6 +100,30 @@ pub mod optional_date {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Dated {
+        #[serde(with = "super::yyyy_mm_dd")]
+        date: NaiveDate,
+    }
+
+    #[test]
+    fn test_yyyy_mm_dd_borrowed_and_owned() {
+        let json = r#"{"date": "2023-09-19"}"#;
+        let borrowed: Dated = serde_json::from_str(json).unwrap();
+        let owned: Dated = serde_json::from_reader(json.as_bytes()).unwrap();
+        let escaped: Dated = serde_json::from_str(r#"{"date": "2023\u002d09-19"}"#).unwrap();
+        for dated in [borrowed, owned, escaped] {
+            assert_eq!(dated.date, NaiveDate::from_ymd_opt(2023, 9, 19).unwrap());
+        }
+        assert!(serde_json::from_str::<Dated>(r#"{"date": "19/09/2023"}"#).is_err());
+    }
+}
+
 pub mod iso_timestamp_string {
     use chrono::{DateTime, FixedOffset, Utc};
     use core::fmt;
@@ -66,12 +133,17 @@ pub mod iso_timestamp_string {
     const FORMAT_SER: &str = "%Y-%m-%d %H:%M:%S%:::z";
     const FORMAT_DE: &str = "%Y-%m-%d %H:%M:%S%#z"; // hack; workaround some bug in chrono
 
+    /// Format a UTC `DateTime` as a timestamp string like "2013-07-31 09:26:16-05".
+    pub fn format(dt: &DateTime<Utc>) -> String {
+        dt.format(FORMAT_SER).to_string()
+    }
+
     /// Serialize a UTC `DateTime` to a timestamp string in the format of "2013-07-31 09:26:16-05".
     pub fn serialize<S>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: ser::Serializer,
     {
-        serializer.serialize_str(&dt.format(FORMAT_SER).to_string())
+        serializer.serialize_str(&format(dt))
     }
 
     /// Deserialize timestamp string such as "2013-07-31 09:26:16-05" into a UTC `DateTime`.
@@ -115,3 +187,34 @@ pub mod iso_timestamp_string {
         }
     }
 }
+
+pub mod optional_iso_timestamp_string {
+    use chrono::{DateTime, Utc};
+    use serde::{de, ser, Deserialize};
+
+    pub fn serialize<S>(dt: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match dt {
+            Some(dt) => super::iso_timestamp_string::serialize(dt, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserialize an optional timestamp string like "2013-07-31 09:26:16-05", treating an empty
+    /// one as absent.
+    pub fn deserialize<'de, D>(d: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(d)? {
+            None => Ok(None),
+            Some(s) if s.is_empty() => Ok(None),
+            Some(s) => {
+                super::iso_timestamp_string::deserialize(de::value::StrDeserializer::new(&s))
+                    .map(Some)
+            }
+        }
+    }
+}
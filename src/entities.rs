@@ -1,4 +1,6 @@
-use crate::date_formats::{iso_timestamp_string, optional_date, yyyy_mm_dd};
+use crate::date_formats::{
+    iso_timestamp_string, optional_date, optional_iso_timestamp_string, yyyy_mm_dd,
+};
 use chrono::{DateTime, NaiveDate, Utc};
 use serde::{self, Deserialize, Serialize};
 
@@ -7,14 +9,98 @@ pub struct RealtimeObservation {
     #[serde(with = "yyyy_mm_dd")]
     pub date: NaiveDate,
     pub value: String,
+    /// The realtime period FRED published the value for, if known. Rows cached before it was
+    /// stored don't have one.
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub realtime_start: Option<NaiveDate>,
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub realtime_end: Option<NaiveDate>,
+    /// The value as the number the cache stored with `--numeric-values`, if it did. Whatever
+    /// changes `value` must clear it.
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub value_real: Option<f64>,
+}
+
+impl RealtimeObservation {
+    /// The value as a number, or `None` if it is missing (`.`) or otherwise not one. Read from
+    /// `value_real` when the cache stored it, saving parsing `value`.
+    pub fn numeric_value(&self) -> Option<f64> {
+        self.value_real.or_else(|| self.value.parse().ok())
+    }
+}
+
+/// Summary of the numeric values among some observations, skipping missing ones (`"."`). The
+/// values are as written, so they keep their precision.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ObservationSummary {
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// The value of the latest numeric observation
+    pub last: Option<String>,
+    /// How many observations have numeric values
+    pub count: usize,
+    /// How many observations there were before `limit` and `offset` cut out a window of them
+    pub total: Option<usize>,
+}
+
+impl ObservationSummary {
+    /// Summarize observations ordered oldest first.
+    pub fn of(observations: &[RealtimeObservation]) -> Self {
+        let mut summary = ObservationSummary::default();
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        for observation in observations {
+            let Some(value) = observation.numeric_value() else {
+                continue;
+            };
+            if value < min {
+                min = value;
+                summary.min = Some(observation.value.clone());
+            }
+            if value > max {
+                max = value;
+                summary.max = Some(observation.value.clone());
+            }
+            summary.last = Some(observation.value.clone());
+            summary.count += 1;
+        }
+        summary
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GetSeriesParams {
     pub series_id: String,
+
+    /// Return FRED's response as FRED sent it, bypassing the cache.
+    #[serde(default)]
+    pub raw: bool,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct GetSeriesOverviewParams {
+    pub series_id: String,
+}
+
+/// Response of `/v0/series/overview`: what a dashboard tile shows about a series
+#[derive(Debug, Serialize)]
+pub struct SeriesOverview {
+    pub metadata: FredEconomicDataSeries,
+
+    /// The series' latest observation, or `None` if it has none yet
+    pub latest: Option<RealtimeObservation>,
+
+    /// Date of the series' first observation
+    #[serde(with = "yyyy_mm_dd")]
+    pub first_date: NaiveDate,
+
+    pub observation_count: u64,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct GetObservationsParams {
     pub series_id: String,
 
@@ -29,10 +115,846 @@ pub struct GetObservationsParams {
 
     #[serde(default, with = "optional_date")]
     pub realtime_end: Option<NaiveDate>,
+
+    /// Transformation FRED applies to the values. Transformed values bypass the cache.
+    #[serde(default)]
+    pub units: Units,
+
+    /// Lower frequency FRED aggregates the observations to. Aggregates bypass the cache.
+    #[serde(default)]
+    pub frequency: Option<AggregationFrequency>,
+
+    /// How FRED aggregates the observations to `frequency`.
+    #[serde(default)]
+    pub aggregation_method: Option<AggregationMethod>,
+
+    /// Oldest cached data (in seconds since it was fetched) the caller will accept.
+    #[serde(default)]
+    pub max_staleness_secs: Option<u64>,
+
+    /// Wrap the observations together with a summary of the series' metadata.
+    #[serde(default)]
+    pub include_meta: bool,
+
+    #[serde(default)]
+    pub format: ObservationsFormat,
+
+    /// Make CSV output open cleanly in Excel (byte order mark and CRLF line endings).
+    #[serde(default)]
+    pub excel: bool,
+
+    /// Return only the latest this many observations in the date bounds.
+    #[serde(default)]
+    pub tail: Option<usize>,
+
+    /// Change computed locally from the cached values, instead of the values themselves.
+    #[serde(default)]
+    pub transform: Option<Transform>,
+
+    /// Aggregate the observations to a lower frequency locally, instead of through FRED.
+    #[serde(default)]
+    pub resample: Option<ResamplePeriod>,
+
+    /// How `resample` combines the observations in each period.
+    #[serde(default)]
+    pub agg: Aggregation,
+
+    /// Echo the resolved request parameters alongside the observations.
+    #[serde(default)]
+    pub debug: bool,
+
+    /// Flag observations that look like data glitches alongside the observations.
+    #[serde(default)]
+    pub validate: bool,
+
+    /// If the requested range has no observations, return the latest cached one before it.
+    #[serde(default)]
+    pub fallback_to_latest: bool,
+
+    /// Index values to 100 at the observation on this date.
+    #[serde(default, with = "optional_date")]
+    pub rebase_date: Option<NaiveDate>,
+
+    /// Index values to 100 at the given observation, instead of at `rebase_date`.
+    #[serde(default)]
+    pub rebase: Option<RebaseAt>,
+
+    /// Round values to this many decimal places.
+    #[serde(default)]
+    pub round: Option<usize>,
+
+    /// Return at most this many observations, with a `next_cursor` to get the rest.
+    #[serde(default)]
+    pub page_size: Option<usize>,
+
+    /// Where to resume paging, as given by the previous page's `next_cursor`.
+    #[serde(default)]
+    pub cursor: Option<String>,
+
+    /// Include the realtime period FRED stamped each value with, which is the day it was fetched
+    /// unless the fetch asked for a realtime period.
+    #[serde(default)]
+    pub include_realtime: bool,
+
+    /// Return FRED's response as FRED sent it, bypassing the cache.
+    #[serde(default)]
+    pub raw: bool,
+
+    /// Answer 304 Not Modified unless FRED has updated the series after this timestamp, as
+    /// given by the series' `last_updated`.
+    #[serde(default, with = "optional_iso_timestamp_string")]
+    pub if_modified_since_updated: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pub date_format: DateFormat,
+
+    /// Order of the returned observations by date.
+    #[serde(default)]
+    pub sort_order: SortOrder,
+
+    /// Return at most this many observations, after skipping `offset`, in `sort_order`.
+    #[serde(default)]
+    pub limit: Option<usize>,
+
+    /// Skip this many observations, in `sort_order`, before those returned.
+    #[serde(default)]
+    pub offset: Option<usize>,
+}
+
+impl GetObservationsParams {
+    /// What the request asks FRED to do to the observations before returning them.
+    pub fn fred_transformation(&self) -> FredTransformation {
+        FredTransformation {
+            units: self.units,
+            frequency: self.frequency,
+            aggregation_method: self.aggregation_method,
+        }
+    }
+}
+
+/// Which row wins when there are several for one date of a series: in a FRED response (rows
+/// from different realtime periods), and between a cached observation and a refetched one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DuplicatePolicy {
+    /// The row from the most recent realtime period, i.e. the latest revision. A refetch, being
+    /// at least as recent, replaces the cached observation.
+    #[default]
+    LatestRealtime,
+    /// The row from the earliest realtime period, i.e. the value as first published. A cached
+    /// observation is never replaced.
+    FirstSeen,
+    /// Whichever row arrives last: the last in FRED's response, and a refetch over the cached
+    /// observation.
+    LastWritten,
+}
+
+/// Observation that `rebase` indexes the values to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RebaseAt {
+    /// The first observation with a value
+    First,
+}
+
+/// Changes between consecutive observations that `/v0/observations` can compute itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Absolute change from the previous observation
+    Delta,
+    /// Percent change from the previous observation
+    PctChange,
+}
+
+/// Transformations FRED can apply to observation values, named as in its `units` parameter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// The values as published
+    #[default]
+    Lin,
+    /// Change
+    Chg,
+    /// Change from a year ago
+    Ch1,
+    /// Percent change
+    Pch,
+    /// Percent change from a year ago
+    Pc1,
+    /// Compounded annual rate of change
+    Pca,
+    /// Continuously compounded rate of change
+    Cch,
+    /// Continuously compounded annual rate of change
+    Cca,
+    /// Natural log
+    Log,
+}
+
+impl Units {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Units::Lin => "lin",
+            Units::Chg => "chg",
+            Units::Ch1 => "ch1",
+            Units::Pch => "pch",
+            Units::Pc1 => "pc1",
+            Units::Pca => "pca",
+            Units::Cch => "cch",
+            Units::Cca => "cca",
+            Units::Log => "log",
+        }
+    }
+}
+
+/// Frequencies FRED can aggregate observations to, named as in its `frequency` parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AggregationFrequency {
+    #[serde(rename = "d")]
+    Daily,
+    #[serde(rename = "w")]
+    Weekly,
+    #[serde(rename = "bw")]
+    Biweekly,
+    #[serde(rename = "m")]
+    Monthly,
+    #[serde(rename = "q")]
+    Quarterly,
+    #[serde(rename = "sa")]
+    Semiannual,
+    #[serde(rename = "a")]
+    Annual,
+    #[serde(rename = "wef")]
+    WeeklyEndingFriday,
+    #[serde(rename = "weth")]
+    WeeklyEndingThursday,
+    #[serde(rename = "wew")]
+    WeeklyEndingWednesday,
+    #[serde(rename = "wetu")]
+    WeeklyEndingTuesday,
+    #[serde(rename = "wem")]
+    WeeklyEndingMonday,
+    #[serde(rename = "wesu")]
+    WeeklyEndingSunday,
+    #[serde(rename = "wesa")]
+    WeeklyEndingSaturday,
+    #[serde(rename = "bwew")]
+    BiweeklyEndingWednesday,
+    #[serde(rename = "bwem")]
+    BiweeklyEndingMonday,
+}
+
+impl AggregationFrequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregationFrequency::Daily => "d",
+            AggregationFrequency::Weekly => "w",
+            AggregationFrequency::Biweekly => "bw",
+            AggregationFrequency::Monthly => "m",
+            AggregationFrequency::Quarterly => "q",
+            AggregationFrequency::Semiannual => "sa",
+            AggregationFrequency::Annual => "a",
+            AggregationFrequency::WeeklyEndingFriday => "wef",
+            AggregationFrequency::WeeklyEndingThursday => "weth",
+            AggregationFrequency::WeeklyEndingWednesday => "wew",
+            AggregationFrequency::WeeklyEndingTuesday => "wetu",
+            AggregationFrequency::WeeklyEndingMonday => "wem",
+            AggregationFrequency::WeeklyEndingSunday => "wesu",
+            AggregationFrequency::WeeklyEndingSaturday => "wesa",
+            AggregationFrequency::BiweeklyEndingWednesday => "bwew",
+            AggregationFrequency::BiweeklyEndingMonday => "bwem",
+        }
+    }
+
+    /// How often the aggregated series has observations, whichever day its weeks end on.
+    pub fn frequency(&self) -> Frequency {
+        match self {
+            AggregationFrequency::Daily => Frequency::Daily,
+            AggregationFrequency::Weekly
+            | AggregationFrequency::WeeklyEndingFriday
+            | AggregationFrequency::WeeklyEndingThursday
+            | AggregationFrequency::WeeklyEndingWednesday
+            | AggregationFrequency::WeeklyEndingTuesday
+            | AggregationFrequency::WeeklyEndingMonday
+            | AggregationFrequency::WeeklyEndingSunday
+            | AggregationFrequency::WeeklyEndingSaturday => Frequency::Weekly,
+            AggregationFrequency::Biweekly
+            | AggregationFrequency::BiweeklyEndingWednesday
+            | AggregationFrequency::BiweeklyEndingMonday => Frequency::Biweekly,
+            AggregationFrequency::Monthly => Frequency::Monthly,
+            AggregationFrequency::Quarterly => Frequency::Quarterly,
+            AggregationFrequency::Semiannual => Frequency::Semiannual,
+            AggregationFrequency::Annual => Frequency::Annual,
+        }
+    }
+}
+
+/// How FRED combines the observations in each period of a lower `frequency`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregationMethod {
+    Avg,
+    Sum,
+    /// End of period: the period's last observation
+    Eop,
+}
+
+impl AggregationMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregationMethod::Avg => "avg",
+            AggregationMethod::Sum => "sum",
+            AggregationMethod::Eop => "eop",
+        }
+    }
+}
+
+/// What FRED is asked to do to observations before returning them. The default asks for the
+/// values as published, the only ones the cache holds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FredTransformation {
+    pub units: Units,
+    pub frequency: Option<AggregationFrequency>,
+    pub aggregation_method: Option<AggregationMethod>,
+}
+
+impl FredTransformation {
+    /// Whether FRED would return anything other than the values as published.
+    pub fn transforms(&self) -> bool {
+        *self != FredTransformation::default()
+    }
+}
+
+/// Periods `/v0/observations` can resample observations to, named like FRED's frequency codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ResamplePeriod {
+    #[serde(rename = "m")]
+    Monthly,
+    #[serde(rename = "q")]
+    Quarterly,
+    #[serde(rename = "a")]
+    Annual,
+}
+
+/// How observations within a resampled period are combined
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    #[default]
+    Avg,
+    Sum,
+    /// The period's last observation
+    Last,
+}
+
+/// Response body format of `/v0/observations`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObservationsFormat {
+    #[default]
+    Json,
+    Csv,
+    /// A JSON object of values keyed by date
+    Map,
+}
+
+/// The few series metadata fields needed to label a chart of its observations.
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesSummary {
+    pub id: String,
+    pub title: String,
+    pub units: String,
+    pub frequency: String,
+}
+
+impl From<FredEconomicDataSeries> for SeriesSummary {
+    fn from(value: FredEconomicDataSeries) -> Self {
+        SeriesSummary {
+            id: value.id,
+            title: value.title,
+            units: value.units,
+            frequency: value.frequency,
+        }
+    }
+}
+
+/// Order `/v0/observations` returns observations in, by date
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Oldest first, like FRED
+    #[default]
+    Asc,
+    /// Newest first
+    Desc,
+}
+
+/// How `/v0/observations` writes dates
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFormat {
+    /// `2020-01-01`, like FRED
+    #[default]
+    Date,
+    /// `2020-01-01T00:00:00Z`: midnight UTC, for consumers that expect full timestamps
+    Iso8601,
+}
+
+impl DateFormat {
+    pub fn format(self, date: NaiveDate) -> String {
+        match self {
+            DateFormat::Date => date.format("%Y-%m-%d").to_string(),
+            DateFormat::Iso8601 => date.format("%Y-%m-%dT00:00:00Z").to_string(),
+        }
+    }
+}
+
+/// Observations serialized with their dates written in `date_format`
+#[derive(Debug)]
+pub struct FormattedObservations<'a> {
+    pub observations: &'a [RealtimeObservation],
+    pub date_format: DateFormat,
+    /// Also write each observation's `realtime_start` and `realtime_end` (null if unknown)
+    pub include_realtime: bool,
+}
+
+impl Serialize for FormattedObservations<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct FormattedObservation<'a> {
+            date: String,
+            value: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            realtime_start: Option<Option<String>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            realtime_end: Option<Option<String>>,
+        }
+        let realtime = |date: Option<NaiveDate>| {
+            self.include_realtime
+                .then(|| date.map(|date| date.format("%Y-%m-%d").to_string()))
+        };
+        serializer.collect_seq(
+            self.observations
+                .iter()
+                .map(|observation| FormattedObservation {
+                    date: self.date_format.format(observation.date),
+                    value: &observation.value,
+                    realtime_start: realtime(observation.realtime_start),
+                    realtime_end: realtime(observation.realtime_end),
+                }),
+        )
+    }
+}
+
+/// Observations serialized as a JSON object from date to value, for clients that look values up
+/// by date. Should a date appear more than once, its last value wins.
+#[derive(Debug)]
+pub struct ObservationsByDate<'a> {
+    pub observations: &'a [RealtimeObservation],
+    pub date_format: DateFormat,
+}
+
+impl Serialize for ObservationsByDate<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let by_date: std::collections::BTreeMap<String, &str> = self
+            .observations
+            .iter()
+            .map(|observation| {
+                (
+                    self.date_format.format(observation.date),
+                    observation.value.as_str(),
+                )
+            })
+            .collect();
+        serializer.collect_map(by_date)
+    }
+}
+
+/// FRED requests made while answering a request, for diagnosing slow responses
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamStats {
+    /// Pages of observations fetched from FRED; 0 when served from the cache
+    pub pages: usize,
+    /// Total time spent waiting on FRED
+    pub duration_ms: f64,
+}
+
+/// Where the observations of a response came from (`include_meta`)
+#[derive(Debug, Clone, Serialize)]
+pub struct ObservationsMeta {
+    /// Whether the response was served without asking FRED
+    pub cache_hit: bool,
+    /// The dates of the observations fetched from FRED, in the order fetched
+    pub fetched_ranges: Vec<DateRange>,
+    pub source: ObservationsSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObservationsSource {
+    Cache,
+    Fred,
+    /// Cached observations together with ones fetched from FRED
+    Mixed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DateRange {
+    #[serde(with = "yyyy_mm_dd")]
+    pub start: NaiveDate,
+    #[serde(with = "yyyy_mm_dd")]
+    pub end: NaiveDate,
+}
+
+/// Response body of `/v0/observations` when `include_meta`, `debug`, `validate`, or `page_size`
+/// ask for more than the bare array of observations. Only the parts asked for are present.
+#[derive(Debug, Serialize)]
+pub struct ObservationsEnvelope<'a> {
+    /// The request parameters as the proxy resolved them (`debug`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<GetObservationsParams>,
+    /// What it took to fetch from FRED (`debug`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<UpstreamStats>,
+    /// `include_meta`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<SeriesSummary>,
+    /// `include_meta`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ObservationsMeta>,
+    /// `validate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<ObservationWarning>>,
+    /// `page_size`, when there are more observations after this page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    pub observations: FormattedObservations<'a>,
+}
+
+/// An observation that `validate` suspects is a data glitch. The observation itself is returned
+/// unchanged.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObservationWarning {
+    #[serde(with = "yyyy_mm_dd")]
+    pub date: NaiveDate,
+    pub value: String,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// Negative in a series that shouldn't go negative, such as a price index
+    Negative,
+    /// Extremely far from the series' other observations
+    Outlier,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetReleaseDatesParams {
+    pub release_id: u64,
+
+    #[serde(default)]
+    pub include_release_dates_with_no_data: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetCategoryParams {
+    /// FRED category ID, 0 being the root of the category tree
+    pub category_id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetTagsSeriesParams {
+    /// Comma-delimited tags
+    pub tag_names: String,
+
+    #[serde(default)]
+    pub tag_match: TagMatch,
+}
+
+/// Whether `/v0/tags/series` returns series with all of the given tags, or any of them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetSeriesSearchParams {
+    pub search_text: String,
+
+    #[serde(default)]
+    pub limit: Option<u32>,
+
+    /// One of FRED's orderings, e.g. `search_rank` or `popularity`
+    #[serde(default)]
+    pub order_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetSeriesSearchRelatedTagsParams {
+    pub series_search_text: String,
+
+    /// Semicolon-delimited tags that the matching series must all have
+    pub tag_names: String,
+
+    #[serde(default)]
+    pub exclude_tag_names: Option<String>,
+
+    #[serde(default)]
+    pub limit: Option<u32>,
+
+    #[serde(default)]
+    pub offset: Option<u32>,
+}
+
+/// A FRED tag, i.e. a label such as "usa" or "gdp" attached to series
+/// See: https://fred.stlouisfed.org/docs/api/fred/tags.html
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FredTag {
+    pub name: String,
+    pub group_id: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(with = "iso_timestamp_string")]
+    pub created: DateTime<Utc>,
+    pub popularity: i64,
+    pub series_count: i64,
+}
+
+/// A node of FRED's category tree, such as "Trade Balance"
+/// See: https://fred.stlouisfed.org/docs/api/fred/category.html
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FredCategory {
+    pub id: u64,
+    pub name: String,
+    /// The category above this one, 0 for the categories under the root
+    pub parent_id: u64,
+}
+
+/// Response JSON type from FRED API endpoints that list categories
+#[derive(Default, Debug, Deserialize, Serialize)]
+pub struct FredResponseCategories {
+    pub categories: Vec<FredCategory>,
+}
+
+/// Response JSON type from FRED API endpoints that list tags
+#[derive(Default, Debug, Deserialize, Serialize)]
+pub struct FredResponseTags {
+    pub count: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub tags: Vec<FredTag>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ClearCacheParams {
+    /// Also vacuum the database to shrink its file.
+    #[serde(default)]
+    pub vacuum: bool,
+}
+
+/// Response of `DELETE /v0/cache/series/:series_id`
+#[derive(Debug, Serialize)]
+pub struct SeriesDeletion {
+    pub series_id: String,
+
+    /// How many cached observations and metadata rows were deleted
+    pub deleted: u64,
+}
+
+/// Query parameters of `POST /v0/cache/warm`
+#[derive(Debug, Default, Deserialize)]
+pub struct WarmCacheParams {
+    pub series_id: String,
+
+    #[serde(default, with = "optional_date")]
+    pub observation_start: Option<NaiveDate>,
+
+    #[serde(default, with = "optional_date")]
+    pub observation_end: Option<NaiveDate>,
+}
+
+/// Response of `POST /v0/cache/warm`
+#[derive(Debug, Serialize)]
+pub struct WarmCacheResponse {
+    /// Observations written to the local cache (0 if it already had them)
+    pub rows_written: usize,
+}
+
+/// Query parameters of `/v0/diff`
+#[derive(Debug, Default, Deserialize)]
+pub struct GetDiffParams {
+    pub series_id: String,
+
+    #[serde(default, with = "optional_date")]
+    pub observation_start: Option<NaiveDate>,
+
+    #[serde(default, with = "optional_date")]
+    pub observation_end: Option<NaiveDate>,
+}
+
+/// How the cached observations of a series differ from FRED's current ones
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct CacheDiff {
+    /// Dates both have, but with different values
+    #[serde(serialize_with = "yyyy_mm_dd::serialize_vec")]
+    pub differing_dates: Vec<NaiveDate>,
+    /// Observations FRED has that the cache doesn't
+    pub missing_in_cache: usize,
+    /// Observations the cache has that FRED no longer does
+    pub extra_in_cache: usize,
+}
+
+/// Request body of `POST /v0/observations/at`
+#[derive(Debug, Deserialize)]
+pub struct GetObservationsAtParams {
+    pub series_id: String,
+
+    #[serde(deserialize_with = "yyyy_mm_dd::deserialize_vec")]
+    pub dates: Vec<NaiveDate>,
+}
+
+/// The observation on one date asked for with `POST /v0/observations/at`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObservationAt {
+    #[serde(with = "yyyy_mm_dd")]
+    pub date: NaiveDate,
+    /// `None` if the series has no observation on the date
+    pub value: Option<String>,
+}
+
+/// Request body of `POST /v0/series/tags/aggregate`
+#[derive(Debug, Deserialize)]
+pub struct AggregateSeriesTagsParams {
+    pub series_ids: Vec<String>,
+}
+
+/// Query parameters of `POST /v0/series/tags/aggregate`
+#[derive(Debug, Default, Deserialize)]
+pub struct AggregateSeriesTagsQuery {
+    #[serde(default)]
+    pub on_error: OnError,
+}
+
+/// What a request for several series does when some of them fail
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    /// Answer with what the other series gave, and why each failed series failed
+    #[default]
+    Partial,
+    /// Answer with the first failure instead
+    Fail,
+}
+
+/// Response of `POST /v0/series/tags/aggregate`
+#[derive(Debug, Default, Serialize)]
+pub struct AggregatedTags {
+    pub tags: Vec<AggregatedTag>,
+    /// Why each series left out of `tags` failed, by series ID
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub errors: std::collections::BTreeMap<String, String>,
+}
+
+/// A tag carried by some of the series in a `/v0/series/tags/aggregate` request
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AggregatedTag {
+    pub name: String,
+    pub group_id: String,
+    pub notes: Option<String>,
+    /// FRED's popularity of the tag across all series
+    pub popularity: i64,
+    /// How many of the requested series carry the tag
+    pub count: usize,
+}
+
+/// A series with observations in the local cache
+#[derive(Debug, Clone, Default, Serialize, sqlx::FromRow)]
+pub struct CachedSeries {
+    pub series_id: String,
+
+    /// Date of the earliest cached observation
+    #[serde(with = "yyyy_mm_dd")]
+    pub observation_start: NaiveDate,
+
+    /// Date of the latest cached observation
+    #[serde(with = "yyyy_mm_dd")]
+    pub observation_end: NaiveDate,
+
+    /// How many observations are cached
+    pub count: i64,
+
+    /// When observations of this series were last fetched from FRED
+    #[serde(skip)]
+    pub fetched_at: Option<DateTime<Utc>>,
+
+    /// Seconds since `fetched_at`, or `None` if the fetch time wasn't recorded
+    #[sqlx(skip)]
+    pub age_secs: Option<i64>,
+}
+
+/// Response of `/v0/cache/size`
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheSize {
+    /// Size of the database file, not counting its write-ahead log
+    pub bytes: u64,
+
+    /// How much of that is free pages that a vacuum would return to the filesystem
+    pub free_bytes: u64,
+
+    /// SQLite's `auto_vacuum` mode: `none`, `full`, or `incremental`
+    pub auto_vacuum: String,
+}
+
+/// Response of `/v0/selftest`
+#[derive(Debug, Serialize)]
+pub struct SelfTestResult {
+    pub passed: bool,
+    /// What went wrong, if it didn't pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response of `/healthz`
+#[derive(Debug, Serialize)]
+pub struct Health {
+    /// `ok`, or `unavailable` when the local cache doesn't answer
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether FRED answers, as reported by `/v0/upstream/status`
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamStatus {
+    pub reachable: bool,
+    /// How long FRED took to answer (or fail to)
+    pub latency_ms: f64,
+    #[serde(with = "iso_timestamp_string")]
+    pub checked_at: DateTime<Utc>,
+    /// Why FRED is unreachable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The last error fetching a series from FRED, as listed by `/v0/cache/errors`
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SeriesFetchError {
+    pub series_id: String,
+    /// HTTP status the fetch failed with
+    pub status: u16,
+    pub message: Option<String>,
+    #[serde(with = "iso_timestamp_string")]
+    pub occurred_at: DateTime<Utc>,
 }
 
 /// Error message from the FRED API.
-/// 
+///
 /// See: https://fred.stlouisfed.org/docs/api/fred/errors.html
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct FredResponseError {
@@ -40,6 +962,7 @@ pub struct FredResponseError {
     pub error_code: u16,
 }
 
+/// One row of FRED's observations, keeping only what the proxy uses.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObservationItem {
     #[serde(with = "yyyy_mm_dd")]
@@ -47,6 +970,7 @@ pub struct ObservationItem {
 
     pub value: String,
 
+    /// Tells apart rows for the same date from different realtime periods
     #[serde(with = "yyyy_mm_dd")]
     pub realtime_start: NaiveDate,
 
@@ -64,7 +988,6 @@ pub struct FredResponseObservation {
     #[allow(dead_code)]
     pub realtime_end: NaiveDate,
 
-    #[allow(dead_code)]
     pub count: usize,
 
     #[allow(dead_code)]
@@ -72,6 +995,8 @@ pub struct FredResponseObservation {
 
     pub limit: usize,
 
+    /// FRED occasionally omits this (e.g. during outages), which means there are none.
+    #[serde(default)]
     pub observations: std::vec::Vec<ObservationItem>,
 }
 
@@ -93,6 +1018,27 @@ pub struct FredResponseSeries {
     pub seriess: Vec<FredEconomicDataSeries>,
 }
 
+/// Response JSON type from FRED API `/fred/tags/series`
+/// See: https://fred.stlouisfed.org/docs/api/fred/tags_series.html
+#[derive(Default, Debug, Deserialize)]
+pub struct FredResponseTagsSeries {
+    pub seriess: Vec<FredEconomicDataSeries>,
+}
+
+/// Response JSON type from FRED API `/fred/release/dates`
+/// See: https://fred.stlouisfed.org/docs/api/fred/release_dates.html
+#[derive(Default, Debug, Deserialize)]
+pub struct FredResponseReleaseDates {
+    pub release_dates: Vec<FredReleaseDate>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FredReleaseDate {
+    pub release_id: u64,
+    #[serde(with = "yyyy_mm_dd")]
+    pub date: NaiveDate,
+}
+
 #[derive(Default, Debug, Deserialize, Serialize, Clone, sqlx::FromRow)]
 pub struct FredEconomicDataSeries {
     pub id: String,
@@ -117,9 +1063,50 @@ pub struct FredEconomicDataSeries {
     pub notes: String,
 }
 
+/// How often a series has observations, as given by FRED's `frequency_short` codes. Ordered
+/// from the highest frequency to the lowest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Biweekly,
+    Monthly,
+    Quarterly,
+    Semiannual,
+    Annual,
+}
+
+impl FredEconomicDataSeries {
+    /// `frequency_short` parsed, or `None` for a code this crate doesn't know.
+    pub fn frequency_enum(&self) -> Option<Frequency> {
+        match self.frequency_short.as_str() {
+            "D" => Some(Frequency::Daily),
+            "W" => Some(Frequency::Weekly),
+            "BW" => Some(Frequency::Biweekly),
+            "M" => Some(Frequency::Monthly),
+            "Q" => Some(Frequency::Quarterly),
+            "SA" => Some(Frequency::Semiannual),
+            "A" => Some(Frequency::Annual),
+            _ => None,
+        }
+    }
+
+    /// Whether `seasonal_adjustment_short` says the series is seasonally adjusted (`SA`, or
+    /// its annual-rate and smoothed variants `SAAR` and `SSA`). Unknown codes count as not.
+    pub fn is_seasonally_adjusted(&self) -> bool {
+        matches!(
+            self.seasonal_adjustment_short.as_str(),
+            "SA" | "SAAR" | "SSA"
+        )
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{FredEconomicDataSeries, FredResponseSeries};
+    use super::{
+        FredApiResponse, FredEconomicDataSeries, FredResponseObservation, FredResponseSeries,
+        FredResponseTags, Frequency, ObservationSummary, RealtimeObservation,
+    };
 
     #[test]
     fn test_decode_series_api_result() {
@@ -130,13 +1117,94 @@ mod test {
             serde_json::from_str::<FredResponseSeries>(api_result)
                 .unwrap()
                 .seriess
-                .get(0)
+                .first()
                 .unwrap()
                 .id,
             "SP500"
         );
         let result = serde_json::from_str::<FredResponseSeries>(api_result).unwrap();
-        let economic_data_series: &FredEconomicDataSeries = result.seriess.get(0).unwrap();
+        let economic_data_series: &FredEconomicDataSeries = result.seriess.first().unwrap();
         assert_eq!(economic_data_series.title, "S&P 500");
+        assert_eq!(
+            economic_data_series.frequency_enum(),
+            Some(Frequency::Daily)
+        );
+        assert!(!economic_data_series.is_seasonally_adjusted());
+    }
+
+    #[test]
+    fn test_typed_accessors_on_unknown_codes() {
+        let series = FredEconomicDataSeries {
+            frequency_short: "5Y".to_string(),
+            seasonal_adjustment_short: "".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(series.frequency_enum(), None);
+        assert!(!series.is_seasonally_adjusted());
+        let series = FredEconomicDataSeries {
+            frequency_short: "Q".to_string(),
+            seasonal_adjustment_short: "SAAR".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(series.frequency_enum(), Some(Frequency::Quarterly));
+        assert!(series.is_seasonally_adjusted());
+    }
+
+    #[test]
+    fn test_decode_observations_without_observations_array() {
+        let api_result = r#"{"realtime_start":"2023-09-19","realtime_end":"2023-09-19","observation_start":"1600-01-01","observation_end":"9999-12-31","units":"lin","output_type":1,"file_type":"json","order_by":"observation_date","sort_order":"asc","count":0,"offset":0,"limit":10000}"#;
+        match serde_json::from_str::<FredApiResponse<FredResponseObservation>>(api_result) {
+            Ok(FredApiResponse::Payload(response)) => assert!(response.observations.is_empty()),
+            other => panic!("expected an empty payload, got {:?}", other),
+        }
+        // error payloads must still be recognized as errors
+        let error_result =
+            r#"{"error_code":400,"error_message":"Bad Request.  The series does not exist."}"#;
+        assert!(matches!(
+            serde_json::from_str::<FredApiResponse<FredResponseObservation>>(error_result),
+            Ok(FredApiResponse::ErrorMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_tags_api_result() {
+        // Example from https://fred.stlouisfed.org/docs/api/fred/series_search_related_tags.html
+        let api_result = r#"{"realtime_start":"2013-08-14","realtime_end":"2013-08-14","order_by":"series_count","sort_order":"desc","count":2,"offset":0,"limit":1000,"tags":[{"name":"nation","group_id":"geot","notes":"Country Level","created":"2012-02-27 10:18:19-06","popularity":100,"series_count":12},{"name":"usa","group_id":"geo","notes":null,"created":"2012-02-27 10:18:19-06","popularity":100,"series_count":12}]}"#;
+        let result = serde_json::from_str::<FredResponseTags>(api_result).unwrap();
+        assert_eq!(result.count, 2);
+        assert_eq!(result.tags[0].name, "nation");
+        assert_eq!(result.tags[1].notes, None);
+    }
+
+    #[test]
+    fn test_observation_summary_skips_missing_values() {
+        let observations: Vec<RealtimeObservation> = [
+            ("2023-01-02", "3.10"),
+            ("2023-01-03", "-1"),
+            ("2023-01-04", "12.5"),
+            ("2023-01-05", "."),
+        ]
+        .iter()
+        .map(|(date, value)| RealtimeObservation {
+            date: date.parse().unwrap(),
+            value: value.to_string(),
+            ..Default::default()
+        })
+        .collect();
+        let summary = ObservationSummary::of(&observations);
+        assert_eq!(
+            summary,
+            ObservationSummary {
+                min: Some("-1".to_string()),
+                max: Some("12.5".to_string()),
+                last: Some("12.5".to_string()),
+                count: 3,
+                total: None,
+            }
+        );
+        assert_eq!(
+            ObservationSummary::of(&observations[3..]),
+            ObservationSummary::default()
+        );
     }
 }
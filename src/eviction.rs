@@ -0,0 +1,72 @@
+use crate::AppState;
+use stlouisfed_fred_web_proxy::local_cache::RealtimeObservationsDatabase;
+
+/// Periodically delete the cached observations of series nobody has requested in
+/// `evict_after_days` days, so the database's size follows what is still in use.
+pub async fn run(app_state: AppState, evict_after_days: u64, every: std::time::Duration) {
+    let mut interval = tokio::time::interval(every);
+    loop {
+        interval.tick().await;
+        let evict_after =
+            std::time::Duration::from_secs(evict_after_days.saturating_mul(24 * 60 * 60));
+        let Some(accessed_before) = chrono::Duration::from_std(evict_after)
+            .ok()
+            .and_then(|evict_after| chrono::Utc::now().checked_sub_signed(evict_after))
+        else {
+            return;
+        };
+        match app_state
+            .realtime_observations_db
+            .evict_series_not_accessed_since(accessed_before)
+            .await
+        {
+            Ok(eviction) => {
+                for series_id in &eviction.series_ids {
+                    app_state
+                        .precompressed_responses
+                        .invalidate_series(series_id);
+                }
+                if !eviction.series_ids.is_empty() {
+                    tracing::info!(
+                        "evicted {} observations of {} series not accessed in {evict_after_days} days",
+                        eviction.observations,
+                        eviction.series_ids.len(),
+                    );
+                }
+            }
+            Err(e) => tracing::error!("eviction failed: {e}"),
+        }
+    }
+}
+
+/// Free pages returned to the filesystem per pass of [`run_incremental_vacuum`], between which
+/// other writers get their turn at the database
+const INCREMENTAL_VACUUM_PAGES: u32 = 256;
+
+/// Periodically return the database's free pages, such as those left by eviction, to the
+/// filesystem, a few at a time rather than rewriting the whole file as `vacuum` would.
+pub async fn run_incremental_vacuum(db: RealtimeObservationsDatabase, every: std::time::Duration) {
+    let mut interval = tokio::time::interval(every);
+    loop {
+        interval.tick().await;
+        let mut freed = 0;
+        loop {
+            match db.incremental_vacuum(INCREMENTAL_VACUUM_PAGES).await {
+                Ok(pages) => {
+                    freed += pages;
+                    if pages < u64::from(INCREMENTAL_VACUUM_PAGES) {
+                        break;
+                    }
+                    tokio::task::yield_now().await;
+                }
+                Err(e) => {
+                    tracing::error!("incremental vacuum failed: {e}");
+                    break;
+                }
+            }
+        }
+        if freed > 0 {
+            tracing::info!("incremental vacuum freed {freed} pages");
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
 use chrono::NaiveDate;
 use hyper::StatusCode;
 
@@ -6,11 +9,410 @@ use axum::{
     Json,
 };
 
+use futures::{Stream, TryStreamExt};
+use serde::de::{self, DeserializeOwned};
+use serde::Deserialize;
+
 use crate::entities::{
-    FredApiResponse, FredResponseError, FredResponseObservation, FredResponseSeries,
-    RealtimeObservation,
+    DuplicatePolicy, FredApiResponse, FredEconomicDataSeries, FredReleaseDate,
+    FredResponseCategories, FredResponseError, FredResponseObservation, FredResponseReleaseDates,
+    FredResponseSeries, FredResponseTags, FredResponseTagsSeries, FredTransformation,
+    GetSeriesSearchParams, GetSeriesSearchRelatedTagsParams, ObservationItem, RealtimeObservation,
+    Units,
 };
 
+/// Where FRED's API is served
+pub const FRED_BASE_URL: &str = "https://api.stlouisfed.org/";
+
+/// Spaces out requests so that no more than a set number start per minute: a token bucket
+/// holding one token, refilled every minute divided by that number.
+#[derive(Debug)]
+struct RateLimiter {
+    interval: std::time::Duration,
+    next: std::sync::Mutex<tokio::time::Instant>,
+}
+
+impl RateLimiter {
+    fn per_minute(requests: u32) -> Self {
+        RateLimiter {
+            interval: std::time::Duration::from_secs(60) / requests.max(1),
+            next: std::sync::Mutex::new(tokio::time::Instant::now()),
+        }
+    }
+
+    /// Wait until the next request may start.
+    async fn acquire(&self) {
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let at = (*next).max(tokio::time::Instant::now());
+            *next = at + self.interval;
+            at
+        };
+        tokio::time::sleep_until(at).await;
+    }
+}
+
+/// FRED API keys, taken in turn by successive requests so that each key's rate limit adds up.
+/// Keys FRED rejects are skipped from then on.
+struct ApiKeys {
+    keys: Vec<String>,
+    next: AtomicUsize,
+    rejected: Vec<AtomicBool>,
+    /// FRED's rate limit applies to each key, so each has its own
+    rate_limiters: Vec<Option<RateLimiter>>,
+}
+
+impl ApiKeys {
+    fn new(keys: Vec<String>, requests_per_minute: Option<u32>) -> Self {
+        ApiKeys {
+            rejected: keys.iter().map(|_| AtomicBool::new(false)).collect(),
+            rate_limiters: keys
+                .iter()
+                .map(|_| requests_per_minute.map(RateLimiter::per_minute))
+                .collect(),
+            keys,
+            next: Default::default(),
+        }
+    }
+
+    /// Wait until `key`'s rate limit allows another request.
+    async fn wait_for_turn(&self, key: &str) {
+        let limiter = self
+            .keys
+            .iter()
+            .position(|k| k == key)
+            .and_then(|i| self.rate_limiters[i].as_ref());
+        if let Some(limiter) = limiter {
+            limiter.acquire().await;
+        }
+    }
+
+    /// The key for the next request. If FRED has rejected every key, they are all tried again
+    /// rather than failing every request without asking FRED.
+    fn next(&self) -> &str {
+        let turn = || self.next.fetch_add(1, Ordering::Relaxed) % self.keys.len();
+        for _ in 0..self.keys.len() {
+            let i = turn();
+            if !self.rejected[i].load(Ordering::Relaxed) {
+                return &self.keys[i];
+            }
+        }
+        &self.keys[turn()]
+    }
+
+    fn reject(&self, key: &str) {
+        if let Some(i) = self.keys.iter().position(|k| k == key) {
+            self.rejected[i].store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// Keys are secrets, so are left out of debug output.
+impl std::fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rejected = self
+            .rejected
+            .iter()
+            .filter(|r| r.load(Ordering::Relaxed))
+            .count();
+        f.debug_struct("ApiKeys")
+            .field("keys", &self.keys.len())
+            .field("rejected", &rejected)
+            .finish()
+    }
+}
+
+/// Everything needed to call the FRED API: the HTTP client, API keys, and the host to call.
+#[derive(Debug, Clone)]
+pub struct FredClient {
+    http: reqwest::Client,
+    api_keys: Arc<ApiKeys>,
+    base_url: reqwest::Url,
+    /// Which row to keep of several FRED returns for one date
+    duplicate_policy: DuplicatePolicy,
+    /// How many times to retry a request that failed transiently, e.g. with status 503
+    max_retries: u32,
+    /// Delay before the first retry, doubling with each one after
+    retry_base_delay: std::time::Duration,
+    /// Most requests to start per minute with each API key
+    requests_per_minute: Option<u32>,
+}
+
+impl FredClient {
+    pub fn new(http: reqwest::Client, api_key: impl Into<String>) -> Self {
+        FredClient {
+            http,
+            api_keys: Arc::new(ApiKeys::new(vec![api_key.into()], None)),
+            base_url: reqwest::Url::parse(FRED_BASE_URL).unwrap(),
+            duplicate_policy: DuplicatePolicy::default(),
+            max_retries: 0,
+            retry_base_delay: std::time::Duration::ZERO,
+            requests_per_minute: None,
+        }
+    }
+
+    /// Start at most `requests_per_minute` requests a minute with each API key, making the rest
+    /// wait their turn, to stay under FRED's rate limit.
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.requests_per_minute = Some(requests_per_minute);
+        self.api_keys = Arc::new(ApiKeys::new(
+            self.api_keys.keys.clone(),
+            self.requests_per_minute,
+        ));
+        self
+    }
+
+    /// Retry requests that fail transiently (see [`is_transient`]) up to `max_retries` times,
+    /// waiting about `base_delay` before the first retry and twice as long before each next one.
+    pub fn with_retries(mut self, max_retries: u32, base_delay: std::time::Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_base_delay = base_delay;
+        self
+    }
+
+    /// Take turns among several API keys, instead of the one given to [`FredClient::new`].
+    pub fn with_api_keys(mut self, api_keys: Vec<String>) -> Self {
+        if !api_keys.is_empty() {
+            self.api_keys = Arc::new(ApiKeys::new(api_keys, self.requests_per_minute));
+        }
+        self
+    }
+
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    /// Send requests to another host serving FRED's API, such as a mirror or a mock server.
+    pub fn with_base_url(mut self, mut base_url: reqwest::Url) -> Result<Self, FredApiError> {
+        if base_url.cannot_be_a_base() {
+            return Err(FredApiError {
+                status_code: StatusCode::BAD_REQUEST,
+                error_message: Some(format!("{base_url} can't be used as a base URL")),
+            });
+        }
+        // so that endpoint paths are joined onto the full base path
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        self.base_url = base_url;
+        Ok(self)
+    }
+
+    pub fn base_url(&self) -> &reqwest::Url {
+        &self.base_url
+    }
+
+    /// URL of a FRED API endpoint such as `fred/series`, asking for JSON with our API key
+    fn endpoint(&self, path: &str) -> Result<reqwest::Url, FredApiError> {
+        let mut url = self
+            .base_url
+            .join(path)
+            .map_err(|_| FredApiError::default())?;
+        url.query_pairs_mut()
+            .append_pair("api_key", self.api_keys.next())
+            .append_pair("file_type", "json");
+        Ok(url)
+    }
+
+    /// Send a GET request to the FRED API and decode its JSON response, turning FRED's error
+    /// payloads into a `FredApiError`. Transient failures are retried with backoff, as set by
+    /// [`FredClient::with_retries`], and counted in `fred_proxy_upstream_retries_total`.
+    async fn get<T: DeserializeOwned>(&self, url: reqwest::Url) -> Result<T, FredApiError> {
+        let endpoint = url.path().to_string();
+        let mut retries = 0;
+        loop {
+            match self.get_with_any_key(url.clone()).await {
+                Err(e) if retries < self.max_retries && is_transient(&e) => {
+                    let delay = backoff(self.retry_base_delay, retries);
+                    retries += 1;
+                    metrics::increment_counter!(
+                        "fred_proxy_upstream_retries_total",
+                        "endpoint" => endpoint.clone(),
+                    );
+                    tracing::warn!(
+                        "FRED request failed with {}, retry {retries} of {} in {delay:?}",
+                        e.status_code,
+                        self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => {
+                    // retries are an early sign of FRED degrading, before requests fail outright
+                    if retries > 0 {
+                        tracing::warn!(
+                            endpoint,
+                            retries,
+                            "FRED request {} after retrying",
+                            if result.is_ok() {
+                                "succeeded"
+                            } else {
+                                "failed"
+                            }
+                        );
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// [`FredClient::get`] without retries, except that should FRED reject the API key, the
+    /// request is retried with each other key in turn.
+    async fn get_with_any_key<T: DeserializeOwned>(
+        &self,
+        mut url: reqwest::Url,
+    ) -> Result<T, FredApiError> {
+        for _ in 1..self.api_keys.keys.len() {
+            match self.get_once(url.clone()).await {
+                Err(e) if is_rejected_api_key(&e) => {
+                    let rejected = url
+                        .query_pairs()
+                        .find(|(name, _)| name == "api_key")
+                        .map(|(_, key)| key.into_owned())
+                        .unwrap_or_default();
+                    tracing::warn!("FRED rejected API key {}", redacted_api_key(&rejected));
+                    self.api_keys.reject(&rejected);
+                    url = with_api_key(&url, self.api_keys.next());
+                }
+                result => return result,
+            }
+        }
+        self.get_once(url).await
+    }
+
+    /// Send one request to FRED, recording it in the `fred_proxy_upstream_*` metrics by
+    /// endpoint, e.g. `/fred/series/observations`.
+    async fn get_once<T: DeserializeOwned>(&self, url: reqwest::Url) -> Result<T, FredApiError> {
+        if let Some((_, key)) = url.query_pairs().find(|(name, _)| name == "api_key") {
+            self.api_keys.wait_for_turn(&key).await;
+        }
+        let endpoint = url.path().to_string();
+        let started = std::time::Instant::now();
+        let result = self.get_response(url).await;
+        metrics::histogram!(
+            "fred_proxy_upstream_request_duration_seconds",
+            started.elapsed(),
+            "endpoint" => endpoint.clone(),
+        );
+        metrics::increment_counter!("fred_proxy_upstream_requests_total", "endpoint" => endpoint.clone());
+        if let Err(e) = &result {
+            metrics::increment_counter!(
+                "fred_proxy_upstream_errors_total",
+                "endpoint" => endpoint,
+                "status" => e.status_code.as_u16().to_string(),
+            );
+        }
+        result
+    }
+
+    async fn get_response<T: DeserializeOwned>(
+        &self,
+        url: reqwest::Url,
+    ) -> Result<T, FredApiError> {
+        let response = self.http.get(url).send().await?;
+        // During maintenance windows FRED answers with an HTML page, sometimes with status 200.
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if !is_json_content_type(content_type) {
+            tracing::warn!(
+                "FRED returned {} with content type {content_type:?} instead of JSON",
+                response.status()
+            );
+            return Err(FredApiError {
+                status_code: StatusCode::BAD_GATEWAY,
+                error_message: Some("upstream returned non-JSON (maintenance?)".to_string()),
+            });
+        }
+        response.json::<FredApiResponse<T>>().await?.into()
+    }
+
+    /// Stream the observations of a series as each page arrives from FRED, oldest first and one
+    /// per date like [`request_observations_from_fred`], so that a long series can be processed
+    /// without holding all of it in memory.
+    pub fn stream_observations<'a>(
+        &'a self,
+        series_id: &'a str,
+        observation_start: Option<NaiveDate>,
+        observation_end: Option<NaiveDate>,
+        realtime_start: Option<NaiveDate>,
+        realtime_end: Option<NaiveDate>,
+        transformation: FredTransformation,
+    ) -> impl Stream<Item = Result<RealtimeObservation, FredApiError>> + 'a {
+        self.stream_observation_pages(
+            series_id,
+            observation_start,
+            observation_end,
+            realtime_start,
+            realtime_end,
+            transformation,
+        )
+        .map_ok(|page| futures::stream::iter(page.observations.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    /// Stream the observations of a series a page at a time, one page per request to FRED.
+    /// Each date's observation is in exactly one page, even when FRED's rows for the date span
+    /// two of its pages, so the pages join up without duplicates whether the caller keeps them or
+    /// not.
+    pub fn stream_observation_pages<'a>(
+        &'a self,
+        series_id: &'a str,
+        observation_start: Option<NaiveDate>,
+        observation_end: Option<NaiveDate>,
+        realtime_start: Option<NaiveDate>,
+        realtime_end: Option<NaiveDate>,
+        transformation: FredTransformation,
+    ) -> impl Stream<Item = Result<ObservationsPage, FredApiError>> + 'a {
+        struct Paging {
+            offset: usize,
+            done: bool,
+            /// Rows of the last date seen, which may continue on the next page
+            held: Vec<ObservationItem>,
+        }
+        let paging = Paging {
+            offset: 0,
+            done: false,
+            held: Vec::new(),
+        };
+        futures::stream::try_unfold(paging, move |mut paging| async move {
+            if paging.done {
+                return Ok::<_, FredApiError>(None);
+            }
+            let url = observations_url(
+                self,
+                series_id,
+                observation_start,
+                observation_end,
+                realtime_start,
+                realtime_end,
+                transformation,
+            )?;
+            let page = request_observations_page(self, url, paging.offset).await?;
+            // A short page is the last one. Otherwise the next page starts a full page further
+            // on, per FRED's paging contract, however many rows this one held.
+            paging.done = page.observations.len() < OBSERVATIONS_PAGE_LIMIT;
+            paging.offset += OBSERVATIONS_PAGE_LIMIT;
+            let mut items = std::mem::take(&mut paging.held);
+            items.extend(page.observations);
+            if !paging.done {
+                if let Some(last_date) = items.iter().map(|item| item.date).max() {
+                    (items, paging.held) =
+                        items.into_iter().partition(|item| item.date < last_date);
+                }
+            }
+            let page = ObservationsPage {
+                observations: collapse_duplicate_dates(items, self.duplicate_policy),
+                count: page.count,
+            };
+            Ok(Some((page, paging)))
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct FredApiError {
     pub status_code: StatusCode,
@@ -44,15 +446,76 @@ impl std::fmt::Display for FredApiError {
     }
 }
 
+/// Network failures have no status of their own: timeouts are reported as 504, and failures to
+/// connect or to decode FRED's response as 502.
 impl From<reqwest::Error> for FredApiError {
     fn from(value: reqwest::Error) -> Self {
+        let status_code = if value.is_timeout() {
+            StatusCode::GATEWAY_TIMEOUT
+        } else if value.is_connect() || value.is_decode() {
+            StatusCode::BAD_GATEWAY
+        } else {
+            value.status().unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        };
         FredApiError {
-            status_code: value.status().unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-            error_message: Some(value.to_string()),
+            status_code,
+            // the URL has the API key in it
+            error_message: Some(value.without_url().to_string()),
         }
     }
 }
 
+/// Whether a request may succeed if tried again: FRED's rate limit, server errors, and
+/// timeouts. Not 502, which is what failures to connect (e.g. DNS) and responses that can't be
+/// decoded are reported as, since trying again won't change them.
+pub fn is_transient(e: &FredApiError) -> bool {
+    matches!(
+        e.status_code,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// How long to wait before retry number `retry` (from 0): `base_delay` doubled for each retry
+/// before it, less a random part of up to half, so that clients failing together don't retry
+/// together.
+fn backoff(base_delay: std::time::Duration, retry: u32) -> std::time::Duration {
+    let delay = base_delay.saturating_mul(1 << retry.min(16));
+    delay - delay.mul_f64(fastrand::f64() / 2.0)
+}
+
+/// Whether FRED refused a request because of its API key, e.g. one that was revoked
+fn is_rejected_api_key(e: &FredApiError) -> bool {
+    matches!(
+        e.status_code,
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+    ) && e
+        .error_message
+        .as_deref()
+        .is_some_and(|message| message.contains("api_key"))
+}
+
+/// `url` with its `api_key` replaced
+fn with_api_key(url: &reqwest::Url, api_key: &str) -> reqwest::Url {
+    let mut replaced = url.clone();
+    replaced
+        .query_pairs_mut()
+        .clear()
+        .extend_pairs(url.query_pairs().map(|(name, value)| match name.as_ref() {
+            "api_key" => (name, api_key.into()),
+            _ => (name, value),
+        }));
+    replaced
+}
+
+/// Enough of an API key to tell which one is meant in logs, without revealing it
+fn redacted_api_key(api_key: &str) -> String {
+    let shown: String = api_key.chars().take(4).collect();
+    format!("{shown}…")
+}
+
 impl<T> From<FredApiResponse<T>> for Result<T, FredApiError> {
     fn from(value: FredApiResponse<T>) -> Self {
         match value {
@@ -76,109 +539,833 @@ impl From<FredResponseError> for FredApiError {
     }
 }
 
+/// Errors are returned in the same shape as FRED's own, with FRED's message passed through
+/// verbatim when the error came from FRED.
 impl IntoResponse for FredApiError {
     fn into_response(self) -> Response {
+        let error_message = self.error_message.unwrap_or_else(|| {
+            self.status_code
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string()
+        });
         (
             self.status_code,
-            Json(self.error_message.unwrap_or_default()),
+            Json(FredResponseError {
+                error_code: self.status_code.as_u16(),
+                error_message,
+            }),
         )
             .into_response()
     }
 }
 
+/// Observations fetched from FRED, with the number of rows FRED reported for the query
+#[derive(Debug, Default)]
+pub struct FredObservations {
+    pub observations: Vec<RealtimeObservation>,
+    /// FRED's `count`, which exceeds the number of observations when a realtime period gives
+    /// some dates several rows.
+    pub count: usize,
+    /// Number of requests it took to page through the observations
+    pub pages: usize,
+}
+
+/// One request's worth of observations from [`FredClient::stream_observation_pages`]
+#[derive(Debug, Default)]
+pub struct ObservationsPage {
+    pub observations: Vec<RealtimeObservation>,
+    /// FRED's `count` for the whole query
+    pub count: usize,
+}
+
+/// Get all the observations in the date bounds, oldest first and one per date, paging through
+/// FRED's responses and keeping every page.
 pub async fn request_observations_from_fred(
-    client: reqwest::Client,
-    fred_api_key: &str,
+    fred: &FredClient,
+    series_id: &str,
+    observation_start: Option<NaiveDate>,
+    observation_end: Option<NaiveDate>,
+    realtime_start: Option<NaiveDate>,
+    realtime_end: Option<NaiveDate>,
+    transformation: FredTransformation,
+) -> Result<FredObservations, FredApiError> {
+    fred.stream_observation_pages(
+        series_id,
+        observation_start,
+        observation_end,
+        realtime_start,
+        realtime_end,
+        transformation,
+    )
+    .try_fold(
+        FredObservations::default(),
+        |mut fetched, page| async move {
+            fetched.observations.extend(page.observations);
+            fetched.count = page.count;
+            fetched.pages += 1;
+            Ok(fetched)
+        },
+    )
+    .await
+}
+
+/// Any JSON FRED answers with other than its error payload, kept as it was sent
+struct RawResponse(serde_json::Value);
+
+impl<'de> Deserialize<'de> for RawResponse {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        // so that `FredApiResponse` takes it for an error instead
+        if value.get("error_code").is_some() {
+            return Err(de::Error::custom("FRED error payload"));
+        }
+        Ok(RawResponse(value))
+    }
+}
+
+/// Get FRED's `series/observations` response as FRED sent it, with every field and row of it,
+/// in one request.
+pub async fn request_raw_observations_from_fred(
+    fred: &FredClient,
+    series_id: &str,
+    observation_start: Option<NaiveDate>,
+    observation_end: Option<NaiveDate>,
+    realtime_start: Option<NaiveDate>,
+    realtime_end: Option<NaiveDate>,
+    transformation: FredTransformation,
+) -> Result<serde_json::Value, FredApiError> {
+    let url = observations_url(
+        fred,
+        series_id,
+        observation_start,
+        observation_end,
+        realtime_start,
+        realtime_end,
+        transformation,
+    )?;
+    let RawResponse(raw) = fred.get(url).await?;
+    Ok(raw)
+}
+
+/// Get FRED's `series` response as FRED sent it.
+pub async fn request_raw_series_from_fred(
+    fred: &FredClient,
+    series_id: &str,
+) -> Result<serde_json::Value, FredApiError> {
+    let mut url = fred.endpoint("fred/series")?;
+    url.query_pairs_mut().append_pair("series_id", series_id);
+    let RawResponse(raw) = fred.get(url).await?;
+    Ok(raw)
+}
+
+/// Most observations FRED returns per request
+const OBSERVATIONS_PAGE_LIMIT: usize = 10_000;
+
+/// The page of observations at `url` starting `offset` rows in, oldest first
+async fn request_observations_page(
+    fred: &FredClient,
+    mut url: reqwest::Url,
+    offset: usize,
+) -> Result<FredResponseObservation, FredApiError> {
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs
+            .append_pair("limit", &OBSERVATIONS_PAGE_LIMIT.to_string())
+            .append_pair("sort_order", "asc");
+        if offset > 0 {
+            pairs.append_pair("offset", &offset.to_string());
+        }
+        pairs.finish();
+    }
+    fred.get(url).await
+}
+
+/// Get only the latest `count` observations in the date bounds, with a single request for
+/// FRED's newest rows first. They are returned oldest first, like the full fetch.
+#[allow(clippy::too_many_arguments)]
+pub async fn request_latest_observations_from_fred(
+    fred: &FredClient,
     series_id: &str,
     observation_start: Option<NaiveDate>,
     observation_end: Option<NaiveDate>,
     realtime_start: Option<NaiveDate>,
     realtime_end: Option<NaiveDate>,
-) -> Result<Vec<RealtimeObservation>, FredApiError> {
-    let mut observations = Vec::<RealtimeObservation>::new();
-    let mut offset: usize = 0usize;
-    const LIMIT: usize = 10_000;
+    transformation: FredTransformation,
+    count: usize,
+) -> Result<FredObservations, FredApiError> {
+    let mut url = observations_url(
+        fred,
+        series_id,
+        observation_start,
+        observation_end,
+        realtime_start,
+        realtime_end,
+        transformation,
+    )?;
+    url.query_pairs_mut()
+        .append_pair("limit", &count.to_string())
+        .append_pair("sort_order", "desc");
+    let fred_response: FredResponseObservation = fred.get(url).await?;
+    Ok(FredObservations {
+        observations: collapse_duplicate_dates(fred_response.observations, fred.duplicate_policy),
+        count: fred_response.count,
+        pages: 1,
+    })
+}
+
+/// `fred/series/observations` URL for a series and whichever date bounds are given
+fn observations_url(
+    fred: &FredClient,
+    series_id: &str,
+    observation_start: Option<NaiveDate>,
+    observation_end: Option<NaiveDate>,
+    realtime_start: Option<NaiveDate>,
+    realtime_end: Option<NaiveDate>,
+    transformation: FredTransformation,
+) -> Result<reqwest::Url, FredApiError> {
     const FORMAT: &str = "%Y-%m-%d";
-    loop {
-        let mut url =
-            reqwest::Url::parse("https://api.stlouisfed.org/fred/series/observations").unwrap();
-
-        {
-            let mut pairs = url.query_pairs_mut();
-            pairs
-                .append_pair("api_key", fred_api_key)
-                .append_pair("file_type", "json")
-                .append_pair("limit", &LIMIT.to_string())
-                .append_pair("sort_order", "asc")
-                .append_pair("series_id", series_id);
-            if let Some(observation_start) = observation_start {
-                pairs.append_pair(
-                    "observation_start",
-                    &observation_start.format(FORMAT).to_string(),
-                );
+    let mut url = fred.endpoint("fred/series/observations")?;
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs.append_pair("series_id", series_id);
+        let bounds = [
+            ("observation_start", observation_start),
+            ("observation_end", observation_end),
+            ("realtime_start", realtime_start),
+            ("realtime_end", realtime_end),
+        ];
+        for (name, date) in bounds {
+            if let Some(date) = date {
+                pairs.append_pair(name, &date.format(FORMAT).to_string());
             }
-            if let Some(observation_end) = observation_end {
-                pairs.append_pair(
-                    "observation_end",
-                    &observation_end.format(FORMAT).to_string(),
-                );
-            }
-            if let Some(realtime_start) = realtime_start {
-                pairs.append_pair("realtime_start", &realtime_start.format(FORMAT).to_string());
-            }
-            if let Some(realtime_end) = realtime_end {
-                pairs.append_pair("realtime_end", &realtime_end.format(FORMAT).to_string());
-            }
-            if offset > 0 {
-                pairs.append_pair("offset", &offset.to_string());
+        }
+        if transformation.units != Units::Lin {
+            pairs.append_pair("units", transformation.units.as_str());
+        }
+        if let Some(frequency) = transformation.frequency {
+            pairs.append_pair("frequency", frequency.as_str());
+        }
+        if let Some(aggregation_method) = transformation.aggregation_method {
+            pairs.append_pair("aggregation_method", aggregation_method.as_str());
+        }
+        pairs.finish();
+    }
+    Ok(url)
+}
+
+/// FRED sometimes returns several rows for the same date (e.g. from overlapping realtime
+/// periods). Keep only the one `policy` picks for each date, so each date appears once and in
+/// ascending order. Rows from the same realtime period are told apart by their response order.
+fn collapse_duplicate_dates(
+    mut items: Vec<ObservationItem>,
+    policy: DuplicatePolicy,
+) -> Vec<RealtimeObservation> {
+    // stable sorts, so that rows otherwise alike keep their response order
+    match policy {
+        DuplicatePolicy::LatestRealtime | DuplicatePolicy::FirstSeen => {
+            items.sort_by_key(|item| (item.date, item.realtime_start))
+        }
+        DuplicatePolicy::LastWritten => items.sort_by_key(|item| item.date),
+    }
+    let mut observations = Vec::<RealtimeObservation>::with_capacity(items.len());
+    for item in items {
+        match observations.last_mut() {
+            Some(last) if last.date == item.date => {
+                if policy != DuplicatePolicy::FirstSeen {
+                    last.value = item.value;
+                    last.realtime_start = Some(item.realtime_start);
+                    last.realtime_end = Some(item.realtime_end);
+                }
             }
-            pairs.finish();
-        }
-        let fred_response_: Result<FredResponseObservation, FredApiError> = client
-            .get(url)
-            .send()
-            .await?
-            .json::<FredApiResponse<FredResponseObservation>>()
-            .await?
-            .into();
-        let fred_response = fred_response_?;
-        fred_response.observations.iter().for_each(|os| {
-            observations.push(RealtimeObservation {
-                date: os.date,
-                value: os.value.clone(),
-            });
-        });
-        if fred_response.observations.len() >= fred_response.limit {
-            offset += fred_response.observations.len();
-        } else {
-            break;
+            _ => observations.push(RealtimeObservation {
+                date: item.date,
+                value: item.value,
+                realtime_start: Some(item.realtime_start),
+                realtime_end: Some(item.realtime_end),
+                value_real: None,
+            }),
         }
     }
-    Ok(observations)
+    observations
 }
 
 /// Get an economic data series (really, just the metadata).
 /// See: https://fred.stlouisfed.org/docs/api/fred/series.html
 pub async fn request_series_from_fred(
-    client: reqwest::Client,
-    fred_api_key: &str,
+    fred: &FredClient,
     series_id: &str,
 ) -> Result<FredResponseSeries, FredApiError> {
-    let url = reqwest::Url::parse_with_params(
-        "https://api.stlouisfed.org/fred/series",
-        &[
-            ("api_key", fred_api_key),
-            ("file_type", "json"),
-            ("series_id", series_id),
-        ][..],
-    )
-    .map_err(|_| FredApiError::default())?;
-    let output: Result<FredResponseSeries, FredApiError> = client
-        .get(url)
-        .send()
-        .await?
-        .json::<FredApiResponse<FredResponseSeries>>()
-        .await?
-        .into();
-    output
+    let mut url = fred.endpoint("fred/series")?;
+    url.query_pairs_mut().append_pair("series_id", series_id);
+    fred.get(url).await
+}
+
+/// Get the dates a release of economic data was (or is scheduled to be) published.
+/// See: https://fred.stlouisfed.org/docs/api/fred/release_dates.html
+pub async fn request_release_dates_from_fred(
+    fred: &FredClient,
+    release_id: u64,
+    include_release_dates_with_no_data: Option<bool>,
+) -> Result<Vec<FredReleaseDate>, FredApiError> {
+    let mut url = fred.endpoint("fred/release/dates")?;
+    url.query_pairs_mut()
+        .append_pair("limit", "10000")
+        .append_pair("release_id", &release_id.to_string())
+        .extend_pairs(optional_bool_param(
+            "include_release_dates_with_no_data",
+            include_release_dates_with_no_data,
+        ));
+    let output: FredResponseReleaseDates = fred.get(url).await?;
+    Ok(output.release_dates)
+}
+
+/// Get a category of FRED's category tree.
+/// See: https://fred.stlouisfed.org/docs/api/fred/category.html
+pub async fn request_category_from_fred(
+    fred: &FredClient,
+    category_id: u64,
+) -> Result<FredResponseCategories, FredApiError> {
+    let mut url = fred.endpoint("fred/category")?;
+    url.query_pairs_mut()
+        .append_pair("category_id", &category_id.to_string());
+    fred.get(url).await
+}
+
+/// Get the tags of a series.
+/// See: https://fred.stlouisfed.org/docs/api/fred/series_tags.html
+pub async fn request_series_tags_from_fred(
+    fred: &FredClient,
+    series_id: &str,
+) -> Result<FredResponseTags, FredApiError> {
+    let mut url = fred.endpoint("fred/series/tags")?;
+    url.query_pairs_mut().append_pair("series_id", series_id);
+    fred.get(url).await
+}
+
+/// Get the series that carry all of `tag_names` (semicolon-delimited), most popular first.
+/// See: https://fred.stlouisfed.org/docs/api/fred/tags_series.html
+pub async fn request_tags_series_from_fred(
+    fred: &FredClient,
+    tag_names: &str,
+) -> Result<Vec<FredEconomicDataSeries>, FredApiError> {
+    let mut url = fred.endpoint("fred/tags/series")?;
+    url.query_pairs_mut()
+        .append_pair("tag_names", tag_names)
+        .append_pair("limit", "1000")
+        .append_pair("order_by", "popularity")
+        .append_pair("sort_order", "desc");
+    let output: FredResponseTagsSeries = fred.get(url).await?;
+    Ok(output.seriess)
+}
+
+/// Get the series matching a full-text search of their titles, notes, and other attributes.
+/// See: https://fred.stlouisfed.org/docs/api/fred/series_search.html
+pub async fn request_series_search_from_fred(
+    fred: &FredClient,
+    params: &GetSeriesSearchParams,
+) -> Result<FredResponseSeries, FredApiError> {
+    let mut url = fred.endpoint("fred/series/search")?;
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs.append_pair("search_text", &params.search_text);
+        if let Some(limit) = params.limit {
+            pairs.append_pair("limit", &limit.to_string());
+        }
+        if let Some(ref order_by) = params.order_by {
+            pairs.append_pair("order_by", order_by);
+        }
+    }
+    fred.get(url).await
+}
+
+/// Get the tags of series matching a full-text search that also carry all of `tag_names`.
+/// See: https://fred.stlouisfed.org/docs/api/fred/series_search_related_tags.html
+pub async fn request_series_search_related_tags_from_fred(
+    fred: &FredClient,
+    params: &GetSeriesSearchRelatedTagsParams,
+) -> Result<FredResponseTags, FredApiError> {
+    let mut url = fred.endpoint("fred/series/search/related_tags")?;
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs
+            .append_pair("series_search_text", &params.series_search_text)
+            .append_pair("tag_names", &params.tag_names);
+        if let Some(ref exclude_tag_names) = params.exclude_tag_names {
+            pairs.append_pair("exclude_tag_names", exclude_tag_names);
+        }
+        if let Some(limit) = params.limit {
+            pairs.append_pair("limit", &limit.to_string());
+        }
+        if let Some(offset) = params.offset {
+            pairs.append_pair("offset", &offset.to_string());
+        }
+    }
+    fred.get(url).await
+}
+
+fn is_json_content_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    essence.eq_ignore_ascii_case("application/json")
+}
+
+/// Whether `key` looks like a FRED API key: 32 lowercase alphanumeric characters.
+pub fn is_well_formed_api_key(key: &str) -> bool {
+    key.len() == 32
+        && key
+            .chars()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_lowercase())
+}
+
+/// Whether `series_id` looks like a FRED series ID such as `SP500` or `A191RL1Q225SBEA`.
+pub fn is_well_formed_series_id(series_id: &str) -> bool {
+    !series_id.is_empty()
+        && series_id.len() <= 64
+        && series_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Query string pair for one of FRED's optional boolean flags. Unset flags are left out so
+/// FRED applies its own default.
+fn optional_bool_param(name: &str, value: Option<bool>) -> Option<(&str, &'static str)> {
+    value.map(|value| (name, if value { "true" } else { "false" }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        collapse_duplicate_dates, is_json_content_type, is_well_formed_api_key,
+        is_well_formed_series_id, optional_bool_param, request_observations_from_fred,
+        request_series_from_fred, FredClient, RateLimiter, StatusCode,
+    };
+    use crate::entities::{DuplicatePolicy, FredResponseObservation, FredTransformation};
+    use futures::TryStreamExt;
+    use wiremock::{
+        matchers::{method, path, query_param, query_param_is_missing},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[test]
+    fn test_collapse_duplicate_dates() {
+        let api_result = r#"{"realtime_start":"2023-09-19","realtime_end":"2023-09-19","observation_start":"1600-01-01","observation_end":"9999-12-31","units":"lin","output_type":1,"file_type":"json","order_by":"observation_date","sort_order":"asc","count":5,"offset":0,"limit":10000,"observations":[
+            {"realtime_start":"2023-09-01","realtime_end":"2023-09-10","date":"2023-08-01","value":"1.0"},
+            {"realtime_start":"2023-09-11","realtime_end":"2023-09-19","date":"2023-08-01","value":"1.5"},
+            {"realtime_start":"2023-09-01","realtime_end":"2023-09-19","date":"2023-08-02","value":"2.0"},
+            {"realtime_start":"2023-09-11","realtime_end":"2023-09-19","date":"2023-08-03","value":"3.5"},
+            {"realtime_start":"2023-09-01","realtime_end":"2023-09-10","date":"2023-08-03","value":"3.0"}
+        ]}"#;
+        let response = serde_json::from_str::<FredResponseObservation>(api_result).unwrap();
+        let values = |policy| -> Vec<String> {
+            collapse_duplicate_dates(response.observations.clone(), policy)
+                .into_iter()
+                .map(|o| o.value)
+                .collect()
+        };
+        assert_eq!(
+            values(DuplicatePolicy::LatestRealtime),
+            ["1.5", "2.0", "3.5"]
+        );
+        assert_eq!(values(DuplicatePolicy::FirstSeen), ["1.0", "2.0", "3.0"]);
+        assert_eq!(values(DuplicatePolicy::LastWritten), ["1.5", "2.0", "3.0"]);
+        let dates: Vec<String> =
+            collapse_duplicate_dates(response.observations, DuplicatePolicy::LatestRealtime)
+                .iter()
+                .map(|o| o.date.to_string())
+                .collect();
+        assert_eq!(dates, ["2023-08-01", "2023-08-02", "2023-08-03"]);
+    }
+
+    #[test]
+    fn test_endpoint_keeps_base_url_path() {
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(reqwest::Url::parse("http://mirror.example/api").unwrap())
+            .unwrap();
+        assert_eq!(
+            fred.endpoint("fred/series").unwrap().as_str(),
+            "http://mirror.example/api/fred/series?api_key=key&file_type=json"
+        );
+    }
+
+    #[test]
+    fn test_well_formed_identifiers() {
+        assert!(is_well_formed_api_key("abcdef0123456789abcdef0123456789"));
+        assert!(!is_well_formed_api_key("ABCDEF0123456789ABCDEF0123456789"));
+        assert!(!is_well_formed_api_key("abc"));
+        assert!(is_well_formed_series_id("SP500"));
+        assert!(is_well_formed_series_id("A191RL1Q225SBEA"));
+        assert!(!is_well_formed_series_id(""));
+        assert!(!is_well_formed_series_id("SP500&api_key=x"));
+    }
+
+    #[test]
+    fn test_optional_bool_param() {
+        assert_eq!(
+            optional_bool_param("flag", Some(true)),
+            Some(("flag", "true"))
+        );
+        assert_eq!(
+            optional_bool_param("flag", Some(false)),
+            Some(("flag", "false"))
+        );
+        assert_eq!(optional_bool_param("flag", None), None);
+    }
+
+    /// A page of `count` daily observations starting `first_day` days after 2000-01-01
+    fn observations_page(first_day: i64, count: i64) -> serde_json::Value {
+        let start = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let observations: Vec<serde_json::Value> = (first_day..first_day + count)
+            .map(|day| {
+                serde_json::json!({
+                    "realtime_start": "2023-09-19",
+                    "realtime_end": "2023-09-19",
+                    "date": (start + chrono::Duration::days(day)).to_string(),
+                    "value": day.to_string(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "realtime_start": "2023-09-19",
+            "realtime_end": "2023-09-19",
+            "count": 20_005,
+            "offset": first_day,
+            "limit": 10_000,
+            "observations": observations,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pages_through_observations_without_gaps_or_repeats() {
+        let server = MockServer::start().await;
+        let pages = [
+            (None, 0, 10_000),
+            (Some("10000"), 10_000, 10_000),
+            (Some("20000"), 20_000, 5),
+        ];
+        for (offset, first_day, count) in pages {
+            let mock = Mock::given(method("GET")).and(path("/fred/series/observations"));
+            let mock = match offset {
+                Some(offset) => mock.and(query_param("offset", offset)),
+                None => mock.and(query_param_is_missing("offset")),
+            };
+            mock.respond_with(
+                ResponseTemplate::new(200).set_body_json(observations_page(first_day, count)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        }
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap();
+        let fetched = request_observations_from_fred(
+            &fred,
+            "SP500",
+            None,
+            None,
+            None,
+            None,
+            FredTransformation::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fetched.pages, 3);
+        let observations = fetched.observations;
+        assert_eq!(observations.len(), 20_005);
+        let values: Vec<String> = observations.iter().map(|o| o.value.clone()).collect();
+        let expected: Vec<String> = (0..20_005).map(|day: i64| day.to_string()).collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_is_json_content_type() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("application/json; charset=UTF-8"));
+        assert!(!is_json_content_type("text/html; charset=UTF-8"));
+        assert!(!is_json_content_type(""));
+    }
+
+    #[tokio::test]
+    async fn test_html_maintenance_page_is_bad_gateway() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw("<html>Down for maintenance</html>", "text/html"),
+            )
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap();
+        let e = request_observations_from_fred(
+            &fred,
+            "SP500",
+            None,
+            None,
+            None,
+            None,
+            FredTransformation::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(e.status_code, hyper::StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            e.error_message.as_deref(),
+            Some("upstream returned non-JSON (maintenance?)")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fred_error_message_is_preserved() {
+        let server = MockServer::start().await;
+        let message = "Bad Request.  The value for variable observation_start is not a valid date.";
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error_code": 400,
+                "error_message": message,
+            })))
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap();
+        let e = request_observations_from_fred(
+            &fred,
+            "SP500",
+            None,
+            None,
+            None,
+            None,
+            FredTransformation::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(e.status_code, hyper::StatusCode::BAD_REQUEST);
+        assert_eq!(e.error_message.as_deref(), Some(message));
+        let response = axum::response::IntoResponse::into_response(e);
+        assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"error_code": 400, "error_message": message})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streams_observations_across_pages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param_is_missing("offset"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(observations_page(0, 10_000)))
+            .mount(&server)
+            .await;
+        // the last date of the first page was revised, and its new row starts the next page
+        let mut second_page = observations_page(10_000, 4);
+        second_page["observations"].as_array_mut().unwrap().insert(
+            0,
+            serde_json::json!({
+                "realtime_start": "2023-09-20",
+                "realtime_end": "9999-12-31",
+                "date": "2027-05-18",
+                "value": "revised",
+            }),
+        );
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("offset", "10000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(second_page))
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap();
+        let observations: Vec<_> = fred
+            .stream_observations(
+                "SP500",
+                None,
+                None,
+                None,
+                None,
+                FredTransformation::default(),
+            )
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(observations.len(), 10_004);
+        assert_eq!(observations[9_999].date.to_string(), "2027-05-18");
+        assert_eq!(observations[9_999].value, "revised");
+        assert_eq!(observations[10_000].value, "10000");
+    }
+
+    #[tokio::test]
+    async fn test_skips_rejected_api_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("api_key", "revoked"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error_code": 400,
+                "error_message": "Bad Request.  The value for variable api_key is not registered.",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut page = observations_page(0, 1);
+        page["count"] = 1.into();
+        Mock::given(method("GET"))
+            .and(query_param("api_key", "valid"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "revoked")
+            .with_api_keys(vec!["revoked".to_string(), "valid".to_string()])
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap();
+        // the first request is retried with the other key, and later ones skip the revoked key
+        for _ in 0..3 {
+            let fetched = request_observations_from_fred(
+                &fred,
+                "SP500",
+                None,
+                None,
+                None,
+                None,
+                FredTransformation::default(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(fetched.observations.len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let server = MockServer::start().await;
+        let unavailable = serde_json::json!({
+            "error_code": 503,
+            "error_message": "Service Unavailable",
+        });
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(503).set_body_json(unavailable))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        let mut page = observations_page(0, 1);
+        page["count"] = 1.into();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap()
+            .with_retries(3, std::time::Duration::from_millis(1));
+        let fetched = request_observations_from_fred(
+            &fred,
+            "SP500",
+            None,
+            None,
+            None,
+            None,
+            FredTransformation::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fetched.observations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_bad_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error_code": 400,
+                "error_message": "Bad Request.  The series does not exist.",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap()
+            .with_retries(3, std::time::Duration::from_millis(1));
+        let e = request_series_from_fred(&fred, "NOPE").await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_undecodable_or_unreachable() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw("{\"seriess\": [", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap()
+            .with_retries(3, std::time::Duration::from_millis(1));
+        let e = request_series_from_fred(&fred, "SP500").await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_GATEWAY);
+        // nothing listens on port 1
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url("http://127.0.0.1:1/".parse().unwrap())
+            .unwrap()
+            .with_retries(3, std::time::Duration::from_secs(60));
+        let e = request_series_from_fred(&fred, "SP500").await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_spaces_requests() {
+        // one request every 5ms
+        let limiter = RateLimiter::per_minute(12_000);
+        let interval = std::time::Duration::from_millis(5);
+        let started = tokio::time::Instant::now();
+        let requests = (0..200).map(|_| async {
+            limiter.acquire().await;
+            tokio::time::Instant::now()
+        });
+        let mut sent = futures::future::join_all(requests).await;
+        sent.sort();
+        for (i, sent) in sent.into_iter().enumerate() {
+            assert!(sent >= started + interval * i as u32);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_applies_to_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "realtime_start": "2023-09-19",
+                "realtime_end": "2023-09-19",
+                "seriess": [],
+            })))
+            .expect(20)
+            .mount(&server)
+            .await;
+        let fred = FredClient::new(reqwest::Client::new(), "key")
+            .with_base_url(server.uri().parse().unwrap())
+            .unwrap()
+            .with_rate_limit(12_000);
+        let started = std::time::Instant::now();
+        let requests = (0..20).map(|_| request_series_from_fred(&fred, "SP500"));
+        for result in futures::future::join_all(requests).await {
+            result.unwrap();
+        }
+        assert!(started.elapsed() >= std::time::Duration::from_millis(19 * 5));
+    }
 }
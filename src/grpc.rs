@@ -0,0 +1,119 @@
+//! gRPC transport for the same observations and series lookups served over HTTP.
+
+use chrono::NaiveDate;
+use hyper::StatusCode;
+use tonic::{Request, Response, Status};
+
+use stlouisfed_fred_web_proxy::{
+    entities::{FredEconomicDataSeries, GetObservationsParams},
+    fred::FredApiError,
+    iso_timestamp_string,
+};
+
+use crate::{fetch_observations, fetch_series, AppState};
+
+pub mod proto {
+    tonic::include_proto!("fredproxy.v0");
+}
+
+use proto::fred_proxy_server::{FredProxy, FredProxyServer};
+
+pub struct FredProxyService {
+    app_state: AppState,
+}
+
+impl FredProxyService {
+    pub fn new(app_state: AppState) -> FredProxyServer<Self> {
+        FredProxyServer::new(FredProxyService { app_state })
+    }
+}
+
+#[tonic::async_trait]
+impl FredProxy for FredProxyService {
+    async fn get_observations(
+        &self,
+        request: Request<proto::GetObservationsRequest>,
+    ) -> Result<Response<proto::GetObservationsResponse>, Status> {
+        let params = observations_params(request.into_inner())
+            .map_err(|e| Status::invalid_argument(format!("invalid date: {e}")))?;
+        let (observations, _) = fetch_observations(&self.app_state, &params, &Default::default())
+            .await
+            .map_err(status_from_fred_api_error)?;
+        Ok(Response::new(proto::GetObservationsResponse {
+            observations: observations
+                .into_iter()
+                .map(|o| proto::RealtimeObservation {
+                    date: o.date.to_string(),
+                    value: o.value,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_series(
+        &self,
+        request: Request<proto::GetSeriesRequest>,
+    ) -> Result<Response<proto::Series>, Status> {
+        let series = fetch_series(&self.app_state, &request.into_inner().series_id)
+            .await
+            .map_err(status_from_fred_api_error)?;
+        Ok(Response::new(series.into()))
+    }
+}
+
+impl From<FredEconomicDataSeries> for proto::Series {
+    fn from(value: FredEconomicDataSeries) -> Self {
+        proto::Series {
+            id: value.id,
+            last_updated: iso_timestamp_string::format(&value.last_updated),
+            realtime_start: value.realtime_start.to_string(),
+            realtime_end: value.realtime_end.to_string(),
+            title: value.title,
+            observation_start: value.observation_start.to_string(),
+            observation_end: value.observation_end.to_string(),
+            frequency: value.frequency,
+            frequency_short: value.frequency_short,
+            units: value.units,
+            units_short: value.units_short,
+            seasonal_adjustment: value.seasonal_adjustment,
+            seasonal_adjustment_short: value.seasonal_adjustment_short,
+            popularity: value.popularity,
+            notes: value.notes,
+        }
+    }
+}
+
+fn observations_params(
+    request: proto::GetObservationsRequest,
+) -> Result<GetObservationsParams, chrono::ParseError> {
+    Ok(GetObservationsParams {
+        series_id: request.series_id,
+        observation_start: parse_date(request.observation_start.as_deref())?,
+        observation_end: parse_date(request.observation_end.as_deref())?,
+        realtime_start: parse_date(request.realtime_start.as_deref())?,
+        realtime_end: parse_date(request.realtime_end.as_deref())?,
+        max_staleness_secs: request.max_staleness_secs,
+        ..Default::default()
+    })
+}
+
+fn parse_date(date: Option<&str>) -> Result<Option<NaiveDate>, chrono::ParseError> {
+    match date {
+        None | Some("") => Ok(None),
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").map(Some),
+    }
+}
+
+fn status_from_fred_api_error(e: FredApiError) -> Status {
+    let message = e.error_message.unwrap_or_default();
+    match e.status_code {
+        StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::TOO_MANY_REQUESTS => Status::resource_exhausted(message),
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+            Status::unavailable(message)
+        }
+        _ => Status::internal(message),
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+use stlouisfed_fred_web_proxy::{
+    entities::{FredEconomicDataSeries, FredTransformation, RealtimeObservation},
+    fred::{
+        is_well_formed_series_id, request_latest_observations_from_fred, request_series_from_fred,
+        FredClient,
+    },
+};
+
+/// Print a summary of a series as FRED has it, then exit.
+#[derive(clap::Args)]
+pub struct InspectArgs {
+    /// FRED series ID, e.g. SP500
+    series_id: String,
+
+    /// How many of the latest observations to fetch and print
+    #[arg(long, default_value_t = 5)]
+    sample: usize,
+}
+
+impl InspectArgs {
+    /// Problems with the arguments, if any
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !is_well_formed_series_id(&self.series_id) {
+            problems.push(format!(
+                "{:?} is not a valid FRED series ID",
+                self.series_id
+            ));
+        }
+        if self.sample == 0 {
+            problems.push("--sample must be greater than 0".to_string());
+        }
+        problems
+    }
+}
+
+pub async fn run(fred: &FredClient, args: InspectArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let series = request_series_from_fred(fred, &args.series_id)
+        .await?
+        .seriess
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("FRED has no series {}", args.series_id))?;
+    let observations = request_latest_observations_from_fred(
+        fred,
+        &args.series_id,
+        None,
+        None,
+        None,
+        None,
+        FredTransformation::default(),
+        args.sample,
+    )
+    .await?
+    .observations;
+    print!("{}", summarize(&series, &observations));
+    Ok(())
+}
+
+/// Human-readable summary of a series and a sample of its latest observations, oldest first.
+fn summarize(series: &FredEconomicDataSeries, observations: &[RealtimeObservation]) -> String {
+    let mut summary = String::new();
+    let _ = writeln!(summary, "{}: {}", series.id, series.title);
+    let _ = writeln!(summary, "  units:        {}", series.units);
+    let _ = writeln!(
+        summary,
+        "  frequency:    {} ({})",
+        series.frequency, series.seasonal_adjustment_short
+    );
+    let _ = writeln!(
+        summary,
+        "  date span:    {} to {}",
+        series.observation_start, series.observation_end
+    );
+    match observations.last() {
+        Some(latest) => {
+            let _ = writeln!(
+                summary,
+                "  latest value: {} on {}",
+                latest.value, latest.date
+            );
+        }
+        None => {
+            let _ = writeln!(summary, "  latest value: none");
+        }
+    }
+    let _ = writeln!(summary, "  last updated: {}", series.last_updated);
+    for observation in observations {
+        let _ = writeln!(summary, "    {}  {}", observation.date, observation.value);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let series: FredEconomicDataSeries = serde_json::from_value(serde_json::json!({
+            "id": "SP500",
+            "realtime_start": "2023-03-01",
+            "realtime_end": "2023-03-01",
+            "title": "S&P 500",
+            "observation_start": "2013-03-01",
+            "observation_end": "2023-02-28",
+            "frequency": "Daily, Close",
+            "frequency_short": "D",
+            "units": "Index",
+            "units_short": "Index",
+            "seasonal_adjustment": "Not Seasonally Adjusted",
+            "seasonal_adjustment_short": "NSA",
+            "last_updated": "2023-02-28 19:10:58-06",
+            "popularity": 83,
+            "notes": ""
+        }))
+        .unwrap();
+        let observations = [
+            RealtimeObservation {
+                date: "2023-02-27".parse().unwrap(),
+                value: "3982.24".to_string(),
+                ..Default::default()
+            },
+            RealtimeObservation {
+                date: "2023-02-28".parse().unwrap(),
+                value: "3970.15".to_string(),
+                ..Default::default()
+            },
+        ];
+        let summary = summarize(&series, &observations);
+        assert!(summary.starts_with("SP500: S&P 500\n"));
+        assert!(summary.contains("  frequency:    Daily, Close (NSA)\n"));
+        assert!(summary.contains("  date span:    2013-03-01 to 2023-02-28\n"));
+        assert!(summary.contains("  latest value: 3970.15 on 2023-02-28\n"));
+        assert!(summary.ends_with("    2023-02-28  3970.15\n"));
+    }
+}
@@ -1,5 +1,10 @@
+pub mod csv_output;
 mod date_formats;
 pub mod entities;
 pub mod local_cache;
+pub mod response_cache;
+pub mod server_timing;
+pub mod transform;
+pub mod validation;
 pub use date_formats::{iso_timestamp_string, optional_date, yyyy_mm_dd};
 pub mod fred;
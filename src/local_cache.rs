@@ -1,16 +1,156 @@
-use crate::entities::{FredEconomicDataSeries, RealtimeObservation};
-use chrono::NaiveDate;
+use crate::entities::{
+    CacheSize, CachedSeries, DuplicatePolicy, FredEconomicDataSeries, RealtimeObservation,
+    SeriesFetchError,
+};
+use crate::fred::FredApiError;
+use chrono::{DateTime, NaiveDate, Utc};
+use hyper::StatusCode;
 use sqlx::sqlite::{
     SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
 };
 
+/// Why a [`RealtimeObservationsDatabase`] operation failed
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    /// No connection to the database could be had in time, or the database was closed
+    #[error("cache database unavailable: {0}")]
+    Pool(sqlx::Error),
+    /// A statement failed, e.g. by violating a constraint or on a locked database
+    #[error("cache query failed: {0}")]
+    Query(sqlx::Error),
+    /// Creating or upgrading the tables failed
+    #[error("cache migration failed: {0}")]
+    Migration(sqlx::Error),
+    /// A row didn't decode to the type it is read as
+    #[error("cached row failed to decode: {0}")]
+    RowDecode(sqlx::Error),
+    /// The self-test read back something other than what it wrote
+    #[error("{0}")]
+    SelfTest(String),
+}
+
+impl From<sqlx::Error> for CacheError {
+    fn from(value: sqlx::Error) -> Self {
+        match value {
+            sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed => CacheError::Pool(value),
+            sqlx::Error::ColumnDecode { .. }
+            | sqlx::Error::Decode(_)
+            | sqlx::Error::ColumnNotFound(_)
+            | sqlx::Error::ColumnIndexOutOfBounds { .. }
+            | sqlx::Error::TypeNotFound { .. } => CacheError::RowDecode(value),
+            _ => CacheError::Query(value),
+        }
+    }
+}
+
+impl From<CacheError> for FredApiError {
+    /// A database that can't be reached in time is reported as unavailable, so clients retry.
+    /// Other failures are internal errors, without SQL details.
+    fn from(value: CacheError) -> Self {
+        match value {
+            CacheError::Pool(_) => FredApiError {
+                status_code: StatusCode::SERVICE_UNAVAILABLE,
+                error_message: Some("the cache is busy; try again".to_string()),
+            },
+            _ => FredApiError::default(),
+        }
+    }
+}
+
+/// What [`RealtimeObservationsDatabase::evict_series_not_accessed_since`] deleted.
+#[derive(Debug, Default)]
+pub struct Eviction {
+    pub series_ids: Vec<String>,
+    pub observations: u64,
+}
+
+/// Observations written per `insert` statement, binding 9 parameters each, which keeps under
+/// SQLite's limit of 999 parameters per statement in older versions
+const ROWS_PER_INSERT: usize = 110;
+
+/// Start of the series IDs self-tests write under, each run its own. FRED series IDs have no
+/// underscores, so they can't clash.
+const SELF_TEST_SERIES_PREFIX: &str = "__SELFTEST_";
+
+/// Self-tests run by this process so far, to tell concurrent runs apart
+static SELF_TEST_RUNS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Tables keyed by the partition their rows were written under (see
+/// [`RealtimeObservationsDatabase::with_source`]): their definitions, and the columns they had
+/// before partitioning
+const PARTITIONED_TABLES: [(&str, &str, &str); 3] = [
+    (
+        "realtime_observations",
+        r#"
+            series_id text not null,
+            date text not null check (date(`date`) > date('1776-07-04') and date(`date`) < date('9999-12-31')),
+            value text not null,
+            fetched_at timestamp,
+            source text,
+            value_real real,
+            realtime_start date,
+            realtime_end date,
+            partition_source text not null default '',
+            primary key (series_id, partition_source, date)
+        "#,
+        "`series_id`, `date`, `value`, `fetched_at`, `source`, `value_real`, `realtime_start`, `realtime_end`",
+    ),
+    (
+        "series_observation_counts",
+        r#"
+            series_id text not null,
+            observation_start date,
+            observation_end date,
+            count integer not null,
+            recorded_at timestamp not null,
+            partition_source text not null default '',
+            primary key (series_id, partition_source)
+        "#,
+        "`series_id`, `observation_start`, `observation_end`, `count`, `recorded_at`",
+    ),
+    (
+        "series_access",
+        r#"
+            series_id text not null,
+            last_accessed_at timestamp not null,
+            partition_source text not null default '',
+            primary key (series_id, partition_source)
+        "#,
+        "`series_id`, `last_accessed_at`",
+    ),
+];
+
+/// Columns of `economic_data_series` added after its first version, with their types
+const SERIES_METADATA_COLUMNS: [(&str, &str); 12] = [
+    ("realtime_start", "date"),
+    ("realtime_end", "date"),
+    ("title", "text"),
+    ("frequency", "text"),
+    ("frequency_short", "text"),
+    ("units", "text"),
+    ("units_short", "text"),
+    ("seasonal_adjustment", "text"),
+    ("seasonal_adjustment_short", "text"),
+    ("popularity", "integer"),
+    ("notes", "text"),
+    ("fetched_at", "timestamp"),
+];
+
 #[derive(Debug, Clone)]
 pub struct RealtimeObservationsDatabase {
     pool: SqlitePool,
+    /// Label stamped on the observations this instance writes, e.g. which deployment fetched them
+    source: Option<String>,
+    /// Whether to keep the observations stamped with `source` apart from the others
+    partition_by_source: bool,
+    /// Whether a write replaces an observation already cached for the date
+    duplicate_policy: DuplicatePolicy,
+    /// Whether to also store values as numbers in `value_real`, for aggregating in SQL
+    numeric_values: bool,
 }
 
 impl RealtimeObservationsDatabase {
-    pub async fn new(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(path: &std::path::Path) -> Result<Self, CacheError> {
         let pathbuf = path.to_path_buf();
         let co: SqliteConnectOptions = SqliteConnectOptions::new()
             .filename(&pathbuf)
@@ -22,28 +162,247 @@ impl RealtimeObservationsDatabase {
             .max_connections(2)
             .acquire_timeout(std::time::Duration::from_secs(30))
             .connect_with(co)
-            .await?;
-        Ok(RealtimeObservationsDatabase { pool })
+            .await
+            .map_err(CacheError::Pool)?;
+        Ok(RealtimeObservationsDatabase {
+            pool,
+            source: None,
+            partition_by_source: false,
+            duplicate_policy: DuplicatePolicy::default(),
+            numeric_values: false,
+        })
     }
 
-    pub async fn create_tables(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let query = r#"
-        create table if not exists realtime_observations (
-            series_id text not null,
-            date text not null check (date(`date`) > date('1776-07-04') and date(`date`) < date('9999-12-31')),
-            value text not null,
-            primary key (series_id, date)
-        );
+    pub fn with_duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = duplicate_policy;
+        self
+    }
+
+    /// Whether a write replaces an observation already cached for the date
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
 
+    /// Also store the values that parse as numbers in `value_real`, leaving it null for missing
+    /// (`.`) and other non-numeric values. `value` keeps FRED's text either way.
+    pub fn with_numeric_values(mut self, numeric_values: bool) -> Self {
+        self.numeric_values = numeric_values;
+        self
+    }
+
+    /// Stamp the observations written with `source`, and if `partition_by_source`, keep them
+    /// and their counts in a partition of their own, so that deployments sharing the database
+    /// neither serve nor overwrite each other's.
+    pub fn with_source(mut self, source: Option<String>, partition_by_source: bool) -> Self {
+        self.source = source;
+        self.partition_by_source = partition_by_source;
+        self
+    }
+
+    /// The partition rows are written under and read from: the source if partitioning by it,
+    /// otherwise the one shared by every deployment that isn't
+    fn partition(&self) -> &str {
+        self.source
+            .as_deref()
+            .filter(|_| self.partition_by_source)
+            .unwrap_or_default()
+    }
+
+    /// Create the tables, and add the columns later versions need to tables created earlier.
+    pub async fn create_tables(&self) -> Result<(), CacheError> {
+        self.migrate().await.map_err(|e| match CacheError::from(e) {
+            CacheError::Query(e) | CacheError::RowDecode(e) => CacheError::Migration(e),
+            e => e,
+        })
+    }
+
+    async fn migrate(&self) -> Result<(), sqlx::Error> {
+        let query = r#"
         create table if not exists economic_data_series (
             id text not null primary key,
             last_updated timestamp not null,
             observation_start date not null,
-            observation_end date not null
+            observation_end date not null,
+            realtime_start date,
+            realtime_end date,
+            title text,
+            frequency text,
+            frequency_short text,
+            units text,
+            units_short text,
+            seasonal_adjustment text,
+            seasonal_adjustment_short text,
+            popularity integer,
+            notes text,
+            fetched_at timestamp
+        );
+
+        create table if not exists series_coverage (
+            series_id text not null,
+            min_requested date,
+            max_requested date,
+            last_updated timestamp,
+            recorded_at timestamp not null,
+            partition_source text not null default ''
+        );
+        create index if not exists series_coverage_series_id on series_coverage (series_id);
+
+        create table if not exists series_fetch_errors (
+            series_id text not null primary key,
+            status integer not null,
+            message text,
+            occurred_at timestamp not null
+        );
+
+        create table if not exists search_cache (
+            key text not null primary key,
+            response text not null,
+            cached_at timestamp not null
+        );
+
+        create table if not exists vintage_fetches (
+            series_id text not null,
+            realtime_start date not null,
+            realtime_end date not null,
+            fetched_at timestamp not null,
+            primary key (series_id, realtime_start, realtime_end)
+        );
+
+        create table if not exists vintage_observations (
+            series_id text not null,
+            realtime_start date not null,
+            realtime_end date not null,
+            date text not null,
+            value text not null,
+            primary key (series_id, realtime_start, realtime_end, date)
         );
         "#;
         let mut conn = self.pool.clone().acquire().await?;
+        for (table, definition, _) in PARTITIONED_TABLES {
+            sqlx::query(&format!(
+                "create table if not exists {table} ({definition})"
+            ))
+            .execute(&mut *conn)
+            .await?;
+        }
         sqlx::query(query).execute(&mut *conn).await?;
+        // Databases created before `fetched_at` existed need the column added.
+        let has_fetched_at: bool = sqlx::query_scalar(
+            "select count(*) > 0 from pragma_table_info('realtime_observations') where name = 'fetched_at'",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if !has_fetched_at {
+            sqlx::query("alter table realtime_observations add column fetched_at timestamp")
+                .execute(&mut *conn)
+                .await?;
+        }
+        // Likewise for `source`, which leaves the rows cached before it unlabeled.
+        let has_source: bool = sqlx::query_scalar(
+            "select count(*) > 0 from pragma_table_info('realtime_observations') where name = 'source'",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if !has_source {
+            sqlx::query("alter table realtime_observations add column source text")
+                .execute(&mut *conn)
+                .await?;
+        }
+        // Likewise for `value_real`, which is filled in below for rows cached before it.
+        let has_value_real: bool = sqlx::query_scalar(
+            "select count(*) > 0 from pragma_table_info('realtime_observations') where name = 'value_real'",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if !has_value_real {
+            sqlx::query("alter table realtime_observations add column value_real real")
+                .execute(&mut *conn)
+                .await?;
+        }
+        // Likewise for the realtime period, which rows cached before it don't have.
+        for column in ["realtime_start", "realtime_end"] {
+            let has_column: bool = sqlx::query_scalar(
+                "select count(*) > 0 from pragma_table_info('realtime_observations') where name = ?",
+            )
+            .bind(column)
+            .fetch_one(&mut *conn)
+            .await?;
+            if !has_column {
+                sqlx::query(&format!(
+                    "alter table realtime_observations add column {column} date"
+                ))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+        if self.numeric_values {
+            // rows cached without numeric values, e.g. before they were turned on
+            sqlx::query(
+                r#"
+            update realtime_observations
+            set `value_real` = cast(`value` as real)
+            where `value_real` is null
+                and `value` glob '*[0-9]*'
+                and `value` not glob '*[^0-9.eE+-]*'
+            "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        // Databases created when only a few columns of series metadata were kept need the rest.
+        for (column, column_type) in SERIES_METADATA_COLUMNS {
+            let has_column: bool = sqlx::query_scalar(
+                "select count(*) > 0 from pragma_table_info('economic_data_series') where name = ?",
+            )
+            .bind(column)
+            .fetch_one(&mut *conn)
+            .await?;
+            if !has_column {
+                sqlx::query(&format!(
+                    "alter table economic_data_series add column {column} {column_type}"
+                ))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+        // Tables created before partitioning by source need it in their primary key, which
+        // SQLite can only change by copying the table. The rows copied are shared by deployments
+        // not partitioning, as they were read before; partitioned ones refetch theirs.
+        for (table, definition, columns) in PARTITIONED_TABLES {
+            let has_partition_source: bool = sqlx::query_scalar(
+                "select count(*) > 0 from pragma_table_info(?) where name = 'partition_source'",
+            )
+            .bind(table)
+            .fetch_one(&mut *conn)
+            .await?;
+            if has_partition_source {
+                continue;
+            }
+            let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+            for statement in [
+                format!("create table {table}_partitioned ({definition})"),
+                format!(
+                    "insert into {table}_partitioned ({columns}) select {columns} from {table}"
+                ),
+                format!("drop table {table}"),
+                format!("alter table {table}_partitioned rename to {table}"),
+            ] {
+                sqlx::query(&statement).execute(&mut *tx).await?;
+            }
+            tx.commit().await?;
+        }
+        let has_partition_source: bool = sqlx::query_scalar(
+            "select count(*) > 0 from pragma_table_info('series_coverage') where name = 'partition_source'",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if !has_partition_source {
+            sqlx::query(
+                "alter table series_coverage add column partition_source text not null default ''",
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
         Ok(())
     }
 
@@ -52,89 +411,1488 @@ impl RealtimeObservationsDatabase {
         series_id: &str,
         since: Option<NaiveDate>,
         until: Option<NaiveDate>,
-    ) -> Result<Vec<RealtimeObservation>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<RealtimeObservation>, CacheError> {
         let query = sqlx::query_as::<_, RealtimeObservation>(
             r#"
-        select `date`, `value`
+        select `date`, `value`, `realtime_start`, `realtime_end`, `value_real`
         from realtime_observations
-        where `series_id` = ?
+        where `series_id` = ?1 and `partition_source` = ?2
         "#,
         );
         let stream = query
-            .bind(&series_id.to_string())
+            .bind(series_id.to_string())
+            .bind(self.partition())
             .fetch_all(&self.pool.clone())
             .await?;
         let since_ = since.unwrap_or(NaiveDate::MIN);
         let until_ = until.unwrap_or(NaiveDate::MAX);
-        let mut within_date_bounds = Vec::<RealtimeObservation>::new();
-        within_date_bounds.reserve(stream.len());
+        let mut within_date_bounds = Vec::<RealtimeObservation>::with_capacity(stream.len());
         stream.iter().for_each(|x| {
             if x.date >= since_ && x.date <= until_ {
                 within_date_bounds.push(x.clone());
             }
         });
-        within_date_bounds.sort_by(|a, b| a.date.cmp(&b.date));
+        within_date_bounds.sort_by_key(|x| x.date);
         Ok(within_date_bounds)
     }
 
+    /// The latest `count` cached observations in the date bounds, oldest first.
+    pub async fn get_latest_observations(
+        &self,
+        series_id: &str,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+        count: usize,
+    ) -> Result<Vec<RealtimeObservation>, CacheError> {
+        let mut observations = sqlx::query_as::<_, RealtimeObservation>(
+            r#"
+        select `date`, `value`, `realtime_start`, `realtime_end`, `value_real`
+        from realtime_observations
+        where `series_id` = ?1
+            and (?2 is null or `date` >= ?2)
+            and (?3 is null or `date` <= ?3)
+            and `partition_source` = ?5
+        order by `date` desc
+        limit ?4
+        "#,
+        )
+        .bind(series_id)
+        .bind(since)
+        .bind(until)
+        .bind(i64::try_from(count).unwrap_or(i64::MAX))
+        .bind(self.partition())
+        .fetch_all(&self.pool)
+        .await?;
+        observations.reverse();
+        Ok(observations)
+    }
+
+    /// How many observations of the series are cached
+    pub async fn count_observations(&self, series_id: &str) -> Result<u64, CacheError> {
+        let count: i64 = sqlx::query_scalar(
+            r#"
+        select count(*)
+        from realtime_observations
+        where `series_id` = ?1 and `partition_source` = ?2
+        "#,
+        )
+        .bind(series_id)
+        .bind(self.partition())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count as u64)
+    }
+
+    /// Whether any cached observation in the date bounds was fetched before `fetched_before`.
+    /// Rows written before fetch times were recorded count as stale.
+    pub async fn has_stale_observations(
+        &self,
+        series_id: &str,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+        fetched_before: DateTime<Utc>,
+    ) -> Result<bool, CacheError> {
+        let stale: bool = sqlx::query_scalar(
+            r#"
+        select count(*) > 0
+        from realtime_observations
+        where `series_id` = ?1
+            and (?2 is null or `date` >= ?2)
+            and (?3 is null or `date` <= ?3)
+            and (`fetched_at` is null or datetime(`fetched_at`) < datetime(?4))
+            and `partition_source` = ?5
+        "#,
+        )
+        .bind(series_id)
+        .bind(since)
+        .bind(until)
+        .bind(fetched_before)
+        .bind(self.partition())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(stale)
+    }
+
+    /// Every series with cached observations, the dates they span and how many there are, and
+    /// how long ago each was last fetched.
+    pub async fn list_cached_series(&self) -> Result<Vec<CachedSeries>, CacheError> {
+        let mut series = sqlx::query_as::<_, CachedSeries>(
+            r#"
+        select `series_id`, min(`date`) as `observation_start`, max(`date`) as `observation_end`,
+            count(*) as `count`, max(`fetched_at`) as `fetched_at`
+        from realtime_observations
+        where `partition_source` = ?
+        group by `series_id`
+        order by `series_id`
+        "#,
+        )
+        .bind(self.partition())
+        .fetch_all(&self.pool)
+        .await?;
+        let now = Utc::now();
+        for s in series.iter_mut() {
+            s.age_secs = s
+                .fetched_at
+                .map(|fetched_at| (now - fetched_at).num_seconds());
+        }
+        Ok(series)
+    }
+
+    /// Remember how many observations FRED reported for a series between `since` and `until`
+    /// (unbounded when `None`), replacing what was known before.
+    pub async fn put_observation_count(
+        &self,
+        series_id: &str,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+        count: usize,
+    ) -> Result<(), CacheError> {
+        sqlx::query(
+            r#"
+        insert or replace into series_observation_counts
+            (`series_id`, `observation_start`, `observation_end`, `count`, `recorded_at`,
+            `partition_source`)
+        values (?, ?, ?, ?, ?, ?)
+        "#,
+        )
+        .bind(series_id)
+        .bind(since)
+        .bind(until)
+        .bind(i64::try_from(count).unwrap_or(i64::MAX))
+        .bind(Utc::now())
+        .bind(self.partition())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether fewer observations are cached in the range of the last count FRED reported for
+    /// the series than that count, e.g. after a partial backfill.
+    pub async fn is_missing_observations(&self, series_id: &str) -> Result<bool, CacheError> {
+        let missing: Option<bool> = sqlx::query_scalar(
+            r#"
+        select c.`count` > (
+            select count(*)
+            from realtime_observations o
+            where o.`series_id` = c.`series_id`
+                and (c.`observation_start` is null or o.`date` >= c.`observation_start`)
+                and (c.`observation_end` is null or o.`date` <= c.`observation_end`)
+                and o.`partition_source` = c.`partition_source`
+        )
+        from series_observation_counts c
+        where c.`series_id` = ?1 and c.`partition_source` = ?2
+        "#,
+        )
+        .bind(series_id)
+        .bind(self.partition())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(missing.unwrap_or(false))
+    }
+
+    /// Whether the last count FRED reported for the series counted it from `first_date` on, so
+    /// that the cached observations, if none are missing, are all of the series' up to the end of
+    /// the count and whatever was cached after it.
+    pub async fn is_counted_from(
+        &self,
+        series_id: &str,
+        first_date: NaiveDate,
+    ) -> Result<bool, CacheError> {
+        let counted: bool = sqlx::query_scalar(
+            r#"
+        select count(*) > 0
+        from series_observation_counts
+        where `series_id` = ?1
+            and (`observation_start` is null or `observation_start` <= ?2)
+            and `partition_source` = ?3
+        "#,
+        )
+        .bind(series_id)
+        .bind(first_date)
+        .bind(self.partition())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(counted)
+    }
+
+    /// Remember that FRED reported no observations of the series between `since` and `until`
+    /// (unbounded when `None`), along with the series' `last_updated` as cached, if it is.
+    /// Ranges the new one covers are forgotten, as are those of the series recorded before
+    /// `expired_before`.
+    pub async fn record_empty_range(
+        &self,
+        series_id: &str,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+        expired_before: DateTime<Utc>,
+    ) -> Result<(), CacheError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+        delete from series_coverage
+        where `series_id` = ?1
+            and (
+                `recorded_at` < ?5
+                or ((?2 is null or (`min_requested` is not null and `min_requested` >= ?2))
+                    and (?3 is null or (`max_requested` is not null and `max_requested` <= ?3)))
+            )
+            and `partition_source` = ?4
+        "#,
+        )
+        .bind(series_id)
+        .bind(since)
+        .bind(until)
+        .bind(self.partition())
+        .bind(expired_before)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+        insert into series_coverage
+            (`series_id`, `min_requested`, `max_requested`, `last_updated`, `recorded_at`,
+            `partition_source`)
+        select ?1, ?2, ?3, (select `last_updated` from economic_data_series where `id` = ?1), ?4,
+            ?5
+        "#,
+        )
+        .bind(series_id)
+        .bind(since)
+        .bind(until)
+        .bind(Utc::now())
+        .bind(self.partition())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Whether FRED reported, no earlier than `recorded_since`, that the series has no
+    /// observations anywhere between `since` and `until` (unbounded when `None`).
+    pub async fn is_range_known_empty(
+        &self,
+        series_id: &str,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+        recorded_since: DateTime<Utc>,
+    ) -> Result<bool, CacheError> {
+        let empty: bool = sqlx::query_scalar(
+            r#"
+        select count(*) > 0
+        from series_coverage
+        where `series_id` = ?1
+            and (`min_requested` is null or (?2 is not null and `min_requested` <= ?2))
+            and (`max_requested` is null or (?3 is not null and `max_requested` >= ?3))
+            and `recorded_at` >= ?4
+            and `partition_source` = ?5
+        "#,
+        )
+        .bind(series_id)
+        .bind(since)
+        .bind(until)
+        .bind(recorded_since)
+        .bind(self.partition())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(empty)
+    }
+
+    /// Delete everything cached, optionally vacuuming the database file afterwards to return
+    /// the space to the filesystem.
+    pub async fn clear_all(&self, vacuum: bool) -> Result<(), CacheError> {
+        let mut tx = self.pool.begin().await?;
+        for table in [
+            "realtime_observations",
+            "economic_data_series",
+            "series_observation_counts",
+            "series_coverage",
+            "series_access",
+            "vintage_fetches",
+            "vintage_observations",
+            "search_cache",
+            "series_fetch_errors",
+        ] {
+            sqlx::query(&format!("delete from {table}"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        if vacuum {
+            sqlx::query("vacuum").execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete the cached observations and metadata of one series, so that it is fetched afresh
+    /// from FRED next time. Returns how many observations and metadata rows were deleted.
+    /// Other partitions' observations are left alone.
+    pub async fn delete_series_observations(&self, series_id: &str) -> Result<u64, CacheError> {
+        let mut tx = self.pool.begin().await?;
+        let observations = sqlx::query(
+            "delete from realtime_observations where `series_id` = ? and `partition_source` = ?",
+        )
+        .bind(series_id)
+        .bind(self.partition())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let series = sqlx::query("delete from economic_data_series where `id` = ?")
+            .bind(series_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        // otherwise a count of none, or a range known to be empty, would keep the next request
+        // from asking FRED
+        for table in ["series_observation_counts", "series_coverage"] {
+            sqlx::query(&format!(
+                "delete from {table} where `series_id` = ? and `partition_source` = ?"
+            ))
+            .bind(series_id)
+            .bind(self.partition())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(observations + series)
+    }
+
+    /// Switch the database to `auto_vacuum = incremental`, so that [`Self::incremental_vacuum`]
+    /// can return free pages to the filesystem. A database created in another mode is
+    /// rewritten once with a full vacuum for the switch to take effect.
+    pub async fn enable_incremental_vacuum(&self) -> Result<(), CacheError> {
+        let mut conn = self.pool.acquire().await?;
+        let mode: i64 = sqlx::query_scalar("pragma auto_vacuum")
+            .fetch_one(&mut *conn)
+            .await?;
+        if mode != 2 {
+            sqlx::query("pragma auto_vacuum = incremental")
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("vacuum").execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+
+    /// Return up to `max_pages` of the database's free pages to the filesystem without
+    /// rewriting the whole file as `vacuum` does, holding the write lock only while they are
+    /// freed. Does nothing unless [`Self::enable_incremental_vacuum`] was called. Returns how
+    /// many pages were freed.
+    pub async fn incremental_vacuum(&self, max_pages: u32) -> Result<u64, CacheError> {
+        let mut conn = self.pool.acquire().await?;
+        let before: i64 = sqlx::query_scalar("pragma freelist_count")
+            .fetch_one(&mut *conn)
+            .await?;
+        // the pragma frees a page per row it steps through; 0 pages would mean all of them
+        sqlx::query(&format!("pragma incremental_vacuum({})", max_pages.max(1)))
+            .fetch_all(&mut *conn)
+            .await?;
+        let after: i64 = sqlx::query_scalar("pragma freelist_count")
+            .fetch_one(&mut *conn)
+            .await?;
+        Ok(before.saturating_sub(after).max(0) as u64)
+    }
+
+    /// How big the database file is and how much of it is free
+    pub async fn size(&self) -> Result<CacheSize, CacheError> {
+        let (bytes, free_bytes, auto_vacuum): (i64, i64, i64) = sqlx::query_as(
+            r#"
+        select page_size * page_count, page_size * freelist_count, auto_vacuum
+        from pragma_page_size, pragma_page_count, pragma_freelist_count, pragma_auto_vacuum;
+        "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(CacheSize {
+            bytes: bytes as u64,
+            free_bytes: free_bytes as u64,
+            auto_vacuum: match auto_vacuum {
+                1 => "full",
+                2 => "incremental",
+                _ => "none",
+            }
+            .to_string(),
+        })
+    }
+
+    /// Note that a request just read `series_id` from the cache, which keeps it from eviction.
+    pub async fn record_access(&self, series_id: &str) -> Result<(), CacheError> {
+        sqlx::query(
+            r#"
+        insert into series_access (`series_id`, `last_accessed_at`, `partition_source`)
+        values (?, ?, ?)
+        on conflict (`series_id`, `partition_source`)
+            do update set `last_accessed_at` = excluded.`last_accessed_at`;
+        "#,
+        )
+        .bind(series_id)
+        .bind(Utc::now())
+        .bind(self.partition())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Delete the cached observations of every series not accessed since `accessed_before`.
+    /// Series cached before access was tracked count as accessed when they were last fetched.
+    /// Only this partition's observations and accesses are considered.
+    pub async fn evict_series_not_accessed_since(
+        &self,
+        accessed_before: DateTime<Utc>,
+    ) -> Result<Eviction, CacheError> {
+        let mut tx = self.pool.begin().await?;
+        let series_ids: Vec<String> = sqlx::query_scalar(
+            r#"
+        select o.`series_id`
+        from realtime_observations o
+        left join series_access a
+            on a.`series_id` = o.`series_id` and a.`partition_source` = o.`partition_source`
+        where o.`partition_source` = ?2
+        group by o.`series_id`
+        having datetime(coalesce(max(a.`last_accessed_at`), max(o.`fetched_at`))) < datetime(?1)
+        "#,
+        )
+        .bind(accessed_before)
+        .bind(self.partition())
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut observations = 0;
+        for series_id in &series_ids {
+            observations += sqlx::query(
+                "delete from realtime_observations where `series_id` = ? and `partition_source` = ?",
+            )
+            .bind(series_id)
+            .bind(self.partition())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            for table in [
+                "series_access",
+                "series_observation_counts",
+                "series_coverage",
+            ] {
+                sqlx::query(&format!(
+                    "delete from {table} where `series_id` = ? and `partition_source` = ?"
+                ))
+                .bind(series_id)
+                .bind(self.partition())
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(Eviction {
+            series_ids,
+            observations,
+        })
+    }
+
+    /// Check that the database answers a trivial query, without touching any table.
+    pub async fn ping(&self) -> Result<(), CacheError> {
+        sqlx::query("select 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Close the connections to the database, waiting for those in use to be returned first.
+    /// Every use of the database fails afterwards.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Write an observation for a reserved series of this run's own, read it back, check it is
+    /// unchanged, and delete it, to show that the database works end to end. Concurrent runs
+    /// don't see each other's rows. Errors say which step failed.
+    pub async fn self_test(&self) -> Result<(), CacheError> {
+        // in case earlier self-tests died halfway; any still running are younger than this
+        sqlx::query(
+            "delete from realtime_observations where `series_id` glob ? and `fetched_at` < ?",
+        )
+        .bind(format!("{SELF_TEST_SERIES_PREFIX}*"))
+        .bind(Utc::now() - chrono::Duration::hours(1))
+        .execute(&self.pool)
+        .await?;
+        let series_id = format!(
+            "{SELF_TEST_SERIES_PREFIX}{}_{}_{}__",
+            std::process::id(),
+            Utc::now().timestamp_micros(),
+            SELF_TEST_RUNS.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+        );
+        let written = RealtimeObservation {
+            date: Utc::now().date_naive(),
+            value: Utc::now().timestamp_micros().to_string(),
+            ..Default::default()
+        };
+        self.put_observations(&series_id, std::slice::from_ref(&written))
+            .await?;
+        let read = self.get_observations(&series_id, None, None).await?;
+        let deleted = sqlx::query("delete from realtime_observations where `series_id` = ?")
+            .bind(&series_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        match read.as_slice() {
+            [read] if read.date == written.date && read.value == written.value => {}
+            _ => {
+                return Err(CacheError::SelfTest(format!(
+                    "read back {read:?} instead of {written:?}"
+                )))
+            }
+        }
+        if deleted != 1 {
+            return Err(CacheError::SelfTest(format!(
+                "deleted {deleted} observations instead of 1"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Write observations of a series, in one transaction and with many rows per statement.
     pub async fn put_observations(
         &self,
         series_id: &str,
         rows: &[RealtimeObservation],
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // let mut conn = self.pool.clone().acquire().await?;
-        for row in rows {
-            let _ = sqlx::query(
+    ) -> Result<(), CacheError> {
+        let fetched_at = Utc::now();
+        let on_conflict = match self.duplicate_policy {
+            DuplicatePolicy::LatestRealtime | DuplicatePolicy::LastWritten => {
+                " on conflict (`series_id`, `partition_source`, `date`) do update set `value` = excluded.`value`, `fetched_at` = excluded.`fetched_at`, `source` = excluded.`source`, `value_real` = excluded.`value_real`, `realtime_start` = excluded.`realtime_start`, `realtime_end` = excluded.`realtime_end`"
+            }
+            // still mark the observation as fetched, so that it doesn't go stale
+            DuplicatePolicy::FirstSeen => {
+                " on conflict (`series_id`, `partition_source`, `date`) do update set `fetched_at` = excluded.`fetched_at`"
+            }
+        };
+        let mut tx = self.pool.begin().await?;
+        for chunk in rows.chunks(ROWS_PER_INSERT) {
+            let mut query = sqlx::QueryBuilder::new(
+                "insert into realtime_observations (`series_id`, `date`, `value`, `fetched_at`, `source`, `value_real`, `realtime_start`, `realtime_end`, `partition_source`) ",
+            );
+            query.push_values(chunk, |mut values, row| {
+                values
+                    .push_bind(series_id)
+                    .push_bind(row.date)
+                    .push_bind(&row.value)
+                    .push_bind(fetched_at)
+                    .push_bind(&self.source)
+                    .push_bind(
+                        self.numeric_values
+                            .then(|| row.value.parse::<f64>().ok())
+                            .flatten()
+                            .filter(|value| value.is_finite()),
+                    )
+                    .push_bind(row.realtime_start)
+                    .push_bind(row.realtime_end)
+                    .push_bind(self.partition());
+            });
+            query.push(on_conflict);
+            // Every full chunk has the same SQL, so it is prepared once and then reused.
+            query.build().persistent(true).execute(&mut *tx).await?;
+        }
+        if let (Some(first), Some(last)) = (
+            rows.iter().map(|row| row.date).min(),
+            rows.iter().map(|row| row.date).max(),
+        ) {
+            // the ranges aren't empty after all
+            sqlx::query(
                 r#"
-            insert into realtime_observations (`series_id`, `date`, `value`)
-            values (?, ?, ?)
-            on conflict (`series_id`, `date`) do update set `value` = excluded.`value`;
+            delete from series_coverage
+            where `series_id` = ?1
+                and (`min_requested` is null or `min_requested` <= ?3)
+                and (`max_requested` is null or `max_requested` >= ?2)
+                and `partition_source` = ?4
             "#,
             )
-            .bind(&series_id.to_string())
-            .bind(row.date)
-            .bind(row.value.clone())
-            .execute(&self.pool.clone())
+            .bind(series_id)
+            .bind(first)
+            .bind(last)
+            .bind(self.partition())
+            .execute(&mut *tx)
             .await?;
         }
+        tx.commit().await?;
         Ok(())
     }
 
-    pub async fn put_series(
+    /// Remember the error fetching a series from FRED failed with, replacing the one before.
+    pub async fn put_fetch_error(
+        &self,
+        series_id: &str,
+        status: u16,
+        message: Option<&str>,
+    ) -> Result<(), CacheError> {
+        sqlx::query(
+            r#"
+        insert or replace into series_fetch_errors (`series_id`, `status`, `message`, `occurred_at`)
+        values (?, ?, ?, ?)
+        "#,
+        )
+        .bind(series_id)
+        .bind(status)
+        .bind(message)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The last error fetching each series from FRED, most recent first.
+    pub async fn list_fetch_errors(&self) -> Result<Vec<SeriesFetchError>, CacheError> {
+        let errors = sqlx::query_as::<_, SeriesFetchError>(
+            r#"
+        select `series_id`, `status`, `message`, `occurred_at`
+        from series_fetch_errors
+        order by `occurred_at` desc
+        "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(errors)
+    }
+
+    /// The cached response (as JSON) of the FRED search identified by `key`, if it was cached
+    /// no earlier than `cached_since`.
+    pub async fn get_search_result(
+        &self,
+        key: &str,
+        cached_since: DateTime<Utc>,
+    ) -> Result<Option<String>, CacheError> {
+        let response: Option<String> = sqlx::query_scalar(
+            r#"
+        select `response`
+        from search_cache
+        where `key` = ?1 and `cached_at` >= ?2
+        "#,
+        )
+        .bind(key)
+        .bind(cached_since)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(response)
+    }
+
+    /// Cache the response (as JSON) of the FRED search identified by `key`, replacing any older one.
+    pub async fn put_search_result(&self, key: &str, response: &str) -> Result<(), CacheError> {
+        sqlx::query(
+            r#"
+        insert or replace into search_cache (`key`, `response`, `cached_at`)
+        values (?, ?, ?)
+        "#,
+        )
+        .bind(key)
+        .bind(response)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The observations of a series as of a past realtime period, in the date bounds, or `None`
+    /// if that vintage of the series isn't cached. Vintages are kept apart from the current
+    /// observations.
+    pub async fn get_vintage_observations(
+        &self,
+        series_id: &str,
+        realtime_start: NaiveDate,
+        realtime_end: NaiveDate,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+    ) -> Result<Option<Vec<RealtimeObservation>>, CacheError> {
+        let mut conn = self.pool.acquire().await?;
+        let fetched: bool = sqlx::query_scalar(
+            r#"
+        select count(*) > 0
+        from vintage_fetches
+        where `series_id` = ?1 and `realtime_start` = ?2 and `realtime_end` = ?3
+        "#,
+        )
+        .bind(series_id)
+        .bind(realtime_start)
+        .bind(realtime_end)
+        .fetch_one(&mut *conn)
+        .await?;
+        if !fetched {
+            return Ok(None);
+        }
+        let observations = sqlx::query_as::<_, RealtimeObservation>(
+            r#"
+        select `date`, `value`
+        from vintage_observations
+        where `series_id` = ?1 and `realtime_start` = ?2 and `realtime_end` = ?3
+            and (?4 is null or `date` >= ?4)
+            and (?5 is null or `date` <= ?5)
+        order by `date`
+        "#,
+        )
+        .bind(series_id)
+        .bind(realtime_start)
+        .bind(realtime_end)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(Some(observations))
+    }
+
+    /// Store every observation of a series as of a past realtime period, which won't change.
+    pub async fn put_vintage_observations(
         &self,
-        series: &FredEconomicDataSeries,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // let mut conn = self.pool.clone().acquire().await?;
+        series_id: &str,
+        realtime_start: NaiveDate,
+        realtime_end: NaiveDate,
+        rows: &[RealtimeObservation],
+    ) -> Result<(), CacheError> {
+        let mut tx = self.pool.begin().await?;
+        for row in rows {
+            sqlx::query(
+                r#"
+            insert or replace into vintage_observations
+                (`series_id`, `realtime_start`, `realtime_end`, `date`, `value`)
+            values (?, ?, ?, ?, ?)
+            "#,
+            )
+            .bind(series_id)
+            .bind(realtime_start)
+            .bind(realtime_end)
+            .bind(row.date)
+            .bind(&row.value)
+            .execute(&mut *tx)
+            .await?;
+        }
         sqlx::query(
             r#"
-        insert into economic_data_series (id, last_updated, observation_start, observation_end)
+        insert or replace into vintage_fetches
+            (`series_id`, `realtime_start`, `realtime_end`, `fetched_at`)
         values (?, ?, ?, ?)
         "#,
         )
+        .bind(series_id)
+        .bind(realtime_start)
+        .bind(realtime_end)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn put_series(&self, series: &FredEconomicDataSeries) -> Result<(), CacheError> {
+        sqlx::query(
+            r#"
+        insert or replace into economic_data_series (
+            id, last_updated, observation_start, observation_end, realtime_start, realtime_end,
+            title, frequency, frequency_short, units, units_short, seasonal_adjustment,
+            seasonal_adjustment_short, popularity, notes, fetched_at
+        )
+        values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        )
         .bind(&series.id)
         .bind(series.last_updated)
         .bind(series.observation_start)
         .bind(series.observation_end)
+        .bind(series.realtime_start)
+        .bind(series.realtime_end)
+        .bind(&series.title)
+        .bind(&series.frequency)
+        .bind(&series.frequency_short)
+        .bind(&series.units)
+        .bind(&series.units_short)
+        .bind(&series.seasonal_adjustment)
+        .bind(&series.seasonal_adjustment_short)
+        .bind(series.popularity)
+        .bind(&series.notes)
+        .bind(Utc::now())
         .execute(&self.pool.clone())
         .await?;
+        // FRED may have added observations to ranges it said were empty since it last updated
+        // the series
+        sqlx::query(
+            r#"
+        delete from series_coverage
+        where `series_id` = ? and (`last_updated` is null or `last_updated` < ?)
+        "#,
+        )
+        .bind(&series.id)
+        .bind(series.last_updated)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
+    /// The cached metadata of `series_id`, if it was fetched from FRED no earlier than
+    /// `fetched_since`. Rows cached before all of the metadata was kept don't count.
     pub async fn get_series(
         &self,
         series_id: &str,
-    ) -> Result<Option<FredEconomicDataSeries>, Box<dyn std::error::Error>> {
+        fetched_since: Option<DateTime<Utc>>,
+    ) -> Result<Option<FredEconomicDataSeries>, CacheError> {
         let mut conn = self.pool.acquire().await?;
         let res: Option<FredEconomicDataSeries> = sqlx::query_as::<_, FredEconomicDataSeries>(
             r#"
-        select id, last_updated, observation_start, observation_end
+        select id, last_updated, observation_start, observation_end, realtime_start, realtime_end,
+            title, frequency, frequency_short, units, units_short, seasonal_adjustment,
+            seasonal_adjustment_short, popularity, notes
         from economic_data_series
-        where id = ?;
+        where id = ?1 and fetched_at is not null and (?2 is null or fetched_at >= ?2);
         "#,
         )
         .bind(series_id)
+        .bind(fetched_since)
         .fetch_optional(&mut *conn)
         .await?;
         Ok(res)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{CacheError, RealtimeObservationsDatabase};
+    use crate::entities::{DuplicatePolicy, FredEconomicDataSeries, RealtimeObservation};
+    use crate::fred::FredApiError;
+    use chrono::{Duration, NaiveDate, Utc};
+
+    #[tokio::test]
+    async fn test_evicts_only_series_not_accessed_recently() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        let rows = [RealtimeObservation {
+            date: "2023-01-03".parse().unwrap(),
+            value: "3".to_string(),
+            ..Default::default()
+        }];
+        db.put_observations("SP500", &rows).await.unwrap();
+        db.put_observations("GDP", &rows).await.unwrap();
+        db.record_access("GDP").await.unwrap();
+        let eviction = db
+            .evict_series_not_accessed_since(Utc::now() - Duration::days(1))
+            .await
+            .unwrap();
+        assert!(eviction.series_ids.is_empty());
+        let cutoff = Utc::now() + Duration::seconds(5);
+        db.record_access("SP500").await.unwrap();
+        sqlx::query("update series_access set last_accessed_at = ? where series_id = 'GDP'")
+            .bind(Utc::now() + Duration::seconds(10))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let eviction = db.evict_series_not_accessed_since(cutoff).await.unwrap();
+        assert_eq!(eviction.series_ids, vec!["SP500".to_string()]);
+        assert_eq!(eviction.observations, 1);
+        assert!(db
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.get_observations("GDP", None, None).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partitions_observations_by_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.db");
+        let db = RealtimeObservationsDatabase::new(&path).await.unwrap();
+        db.create_tables().await.unwrap();
+        let staging = db.clone().with_source(Some("staging".to_string()), true);
+        let production = db.clone().with_source(Some("production".to_string()), true);
+        let row = |value: &str| RealtimeObservation {
+            date: "2023-01-03".parse().unwrap(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        staging
+            .put_observations("SP500", &[row("3")])
+            .await
+            .unwrap();
+        let source: Option<String> = sqlx::query_scalar("select source from realtime_observations")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(source.as_deref(), Some("staging"));
+        assert!(production
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        // each partition keeps its own row of the date, and its own count
+        production
+            .put_observations("SP500", &[row("3.1")])
+            .await
+            .unwrap();
+        staging
+            .put_observation_count("SP500", None, None, 2)
+            .await
+            .unwrap();
+        for (db, value, missing) in [(&staging, "3", true), (&production, "3.1", false)] {
+            let observations = db.get_observations("SP500", None, None).await.unwrap();
+            assert_eq!(observations.len(), 1);
+            assert_eq!(observations[0].value, value);
+            assert_eq!(db.is_missing_observations("SP500").await.unwrap(), missing);
+        }
+        // unpartitioned deployments share the rows written unpartitioned
+        assert!(db
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        let labeled = db.clone().with_source(Some("audit".to_string()), false);
+        labeled
+            .put_observations("SP500", &[row("3.2")])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_observations("SP500", None, None).await.unwrap()[0].value,
+            "3.2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deletes_and_evicts_only_their_own_partition() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        let staging = db.clone().with_source(Some("staging".to_string()), true);
+        let production = db.clone().with_source(Some("production".to_string()), true);
+        let rows = [RealtimeObservation {
+            date: "2023-01-03".parse().unwrap(),
+            value: "3".to_string(),
+            ..Default::default()
+        }];
+        for db in [&staging, &production] {
+            db.put_observations("SP500", &rows).await.unwrap();
+            db.put_observations("GDP", &rows).await.unwrap();
+            db.put_observation_count("SP500", None, None, 1)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            staging.delete_series_observations("SP500").await.unwrap(),
+            1
+        );
+        assert!(staging
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            production
+                .get_observations("SP500", None, None)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(production
+            .is_counted_from("SP500", "2023-01-03".parse().unwrap())
+            .await
+            .unwrap());
+        // production still reads GDP, staging doesn't
+        production.record_access("GDP").await.unwrap();
+        let cutoff = Utc::now() + Duration::seconds(5);
+        sqlx::query("update series_access set last_accessed_at = ?")
+            .bind(Utc::now() + Duration::seconds(10))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let eviction = staging
+            .evict_series_not_accessed_since(cutoff)
+            .await
+            .unwrap();
+        assert_eq!(eviction.series_ids, vec!["GDP".to_string()]);
+        let eviction = production
+            .evict_series_not_accessed_since(cutoff)
+            .await
+            .unwrap();
+        assert_eq!(eviction.series_ids, vec!["SP500".to_string()]);
+        assert_eq!(
+            production
+                .get_observations("GDP", None, None)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partitions_observations_cached_before_partitioning() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        // the tables keyed without the partition, with an observation cached by that version
+        for statement in [
+            "create table realtime_observations (
+                series_id text not null,
+                date text not null,
+                value text not null,
+                primary key (series_id, date)
+            )",
+            "insert into realtime_observations values ('SP500', '2023-01-03', '3')",
+            "create table series_observation_counts (
+                series_id text not null primary key,
+                observation_start date,
+                observation_end date,
+                count integer not null,
+                recorded_at timestamp not null
+            )",
+            "insert into series_observation_counts values ('SP500', null, null, 2, '2023-01-03')",
+        ] {
+            sqlx::query(statement).execute(&db.pool).await.unwrap();
+        }
+        db.create_tables().await.unwrap();
+        let observations = db.get_observations("SP500", None, None).await.unwrap();
+        assert_eq!(observations.len(), 1);
+        assert!(db.is_missing_observations("SP500").await.unwrap());
+        let staging = db.clone().with_source(Some("staging".to_string()), true);
+        staging
+            .put_observations("SP500", &observations)
+            .await
+            .unwrap();
+        assert_eq!(db.count_observations("SP500").await.unwrap(), 1);
+        assert_eq!(staging.count_observations("SP500").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_first_seen_keeps_cached_observations() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap()
+            .with_duplicate_policy(DuplicatePolicy::FirstSeen);
+        db.create_tables().await.unwrap();
+        let row = |value: &str| RealtimeObservation {
+            date: "2023-01-03".parse().unwrap(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        db.put_observations("GDP", &[row("1.0")]).await.unwrap();
+        db.put_observations("GDP", &[row("1.5")]).await.unwrap();
+        let cached = db.get_observations("GDP", None, None).await.unwrap();
+        assert_eq!(cached[0].value, "1.0");
+        let db = db.with_duplicate_policy(DuplicatePolicy::LatestRealtime);
+        db.put_observations("GDP", &[row("1.5")]).await.unwrap();
+        let cached = db.get_observations("GDP", None, None).await.unwrap();
+        assert_eq!(cached[0].value, "1.5");
+    }
+
+    #[tokio::test]
+    async fn test_stores_numeric_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.db");
+        let db = RealtimeObservationsDatabase::new(&path).await.unwrap();
+        db.create_tables().await.unwrap();
+        let row = |date: &str, value: &str| RealtimeObservation {
+            date: date.parse().unwrap(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        db.put_observations("GDP", &[row("2023-01-01", "1.5")])
+            .await
+            .unwrap();
+        // turning numeric values on fills them in for what was cached without them
+        let db = db.with_numeric_values(true);
+        db.create_tables().await.unwrap();
+        db.put_observations("GDP", &[row("2023-04-01", "."), row("2023-07-01", "-2e3")])
+            .await
+            .unwrap();
+        let values: Vec<Option<f64>> =
+            sqlx::query_scalar("select value_real from realtime_observations order by date")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(values, vec![Some(1.5), None, Some(-2000.0)]);
+        let cached = db.get_observations("GDP", None, None).await.unwrap();
+        assert_eq!(cached[1].value, ".");
+        assert_eq!(cached[2].value, "-2e3");
+        let numbers: Vec<Option<f64>> = cached.iter().map(|o| o.value_real).collect();
+        assert_eq!(numbers, vec![Some(1.5), None, Some(-2000.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_self_test_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap()
+            .with_duplicate_policy(DuplicatePolicy::FirstSeen);
+        db.create_tables().await.unwrap();
+        db.self_test().await.unwrap();
+        let (first, second) = tokio::join!(db.self_test(), db.self_test());
+        first.unwrap();
+        second.unwrap();
+        let rows: i64 = sqlx::query_scalar("select count(*) from realtime_observations")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+
+    #[tokio::test]
+    async fn test_incremental_vacuum_on_existing_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        let rows: Vec<_> = (0..2000)
+            .map(|day| RealtimeObservation {
+                date: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap() + Duration::days(day),
+                value: "a value long enough to fill some pages".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        db.put_observations("SP500", &rows).await.unwrap();
+        assert_eq!(db.size().await.unwrap().auto_vacuum, "none");
+        db.enable_incremental_vacuum().await.unwrap();
+        assert_eq!(db.size().await.unwrap().auto_vacuum, "incremental");
+        db.clear_all(false).await.unwrap();
+        let before = db.size().await.unwrap();
+        assert!(before.free_bytes > 0);
+        assert_eq!(db.incremental_vacuum(1).await.unwrap(), 1);
+        while db.incremental_vacuum(100).await.unwrap() > 0 {}
+        let after = db.size().await.unwrap();
+        assert_eq!(after.free_bytes, 0);
+        assert!(after.bytes < before.bytes);
+    }
+
+    #[tokio::test]
+    async fn test_full_series_metadata_survives_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        // the table as first released, with a series cached by that version
+        sqlx::query(
+            "create table economic_data_series (
+                id text not null primary key,
+                last_updated timestamp not null,
+                observation_start date not null,
+                observation_end date not null
+            )",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "insert into economic_data_series
+            values ('GDP', '2023-09-28 07:56:01+00', '1947-01-01', '2023-04-01')",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        db.create_tables().await.unwrap();
+        let legacy: i64 =
+            sqlx::query_scalar("select count(*) from economic_data_series where id = 'GDP'")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(legacy, 1);
+        // without its metadata, the legacy row is refetched rather than served
+        assert!(db.get_series("GDP", None).await.unwrap().is_none());
+
+        let series = FredEconomicDataSeries {
+            id: "SP500".to_string(),
+            last_updated: "2023-09-18T23:10:56Z".parse().unwrap(),
+            realtime_start: "2023-09-19".parse().unwrap(),
+            realtime_end: "2023-09-19".parse().unwrap(),
+            title: "S&P 500".to_string(),
+            observation_start: "2013-09-19".parse().unwrap(),
+            observation_end: "2023-09-18".parse().unwrap(),
+            frequency: "Daily, Close".to_string(),
+            frequency_short: "D".to_string(),
+            units: "Index".to_string(),
+            units_short: "Index".to_string(),
+            seasonal_adjustment: "Not Seasonally Adjusted".to_string(),
+            seasonal_adjustment_short: "NSA".to_string(),
+            popularity: 83,
+            notes:
+                "The observations for the S&P 500 represent the daily index value at market close."
+                    .to_string(),
+        };
+        db.put_series(&series).await.unwrap();
+        let cached = db.get_series("SP500", None).await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&cached).unwrap(),
+            serde_json::to_value(&series).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_puts_observations_in_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        // more rows than fit one statement, and a last statement that isn't full
+        let rows = |value: &str| -> Vec<RealtimeObservation> {
+            (0..20_000)
+                .map(|day| RealtimeObservation {
+                    date: NaiveDate::from_ymd_opt(1950, 1, 1).unwrap() + Duration::days(day),
+                    value: value.to_string(),
+                    ..Default::default()
+                })
+                .collect()
+        };
+        db.put_observations("SP500", &rows("1")).await.unwrap();
+        assert_eq!(db.count_observations("SP500").await.unwrap(), 20_000);
+        db.put_observations("SP500", &rows("2")).await.unwrap();
+        assert_eq!(db.count_observations("SP500").await.unwrap(), 20_000);
+        let latest = db
+            .get_latest_observations("SP500", None, None, 1)
+            .await
+            .unwrap();
+        assert_eq!(latest[0].value, "2");
+    }
+
+    #[tokio::test]
+    async fn test_classifies_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        // the date is outside the table's check constraint
+        let rows = [RealtimeObservation {
+            date: "1700-01-01".parse().unwrap(),
+            value: "3".to_string(),
+            ..Default::default()
+        }];
+        let e = db.put_observations("SP500", &rows).await.unwrap_err();
+        assert!(matches!(e, CacheError::Query(_)), "{e:?}");
+        assert_eq!(
+            FredApiError::from(e).status_code,
+            hyper::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        sqlx::query(
+            "insert into economic_data_series (id, last_updated, observation_start, observation_end, fetched_at) values ('SP500', 'yesterday', 'then', 'now', ?)",
+        )
+        .bind(Utc::now())
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        let e = db.get_series("SP500", None).await.unwrap_err();
+        assert!(matches!(e, CacheError::RowDecode(_)), "{e:?}");
+        db.close().await;
+        let e = db.count_observations("SP500").await.unwrap_err();
+        assert!(matches!(e, CacheError::Pool(_)), "{e:?}");
+        assert_eq!(
+            FredApiError::from(e).status_code,
+            hyper::StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detects_stale_observations_without_an_end_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        let rows = [RealtimeObservation {
+            date: "2023-01-03".parse().unwrap(),
+            value: "3".to_string(),
+            ..Default::default()
+        }];
+        db.put_observations("SP500", &rows).await.unwrap();
+        let stale = |until: Option<NaiveDate>, fetched_before| {
+            db.has_stale_observations("SP500", None, until, fetched_before)
+        };
+        let later = Utc::now() + Duration::hours(1);
+        let earlier = Utc::now() - Duration::hours(1);
+        assert!(stale(None, later).await.unwrap());
+        assert!(stale(Some("2023-01-03".parse().unwrap()), later)
+            .await
+            .unwrap());
+        assert!(!stale(Some("2023-01-02".parse().unwrap()), later)
+            .await
+            .unwrap());
+        assert!(!stale(None, earlier).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_lists_cached_series_with_their_span() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        let rows: Vec<_> = ["2023-01-03", "2023-01-05", "2023-01-04"]
+            .into_iter()
+            .map(|date| RealtimeObservation {
+                date: date.parse().unwrap(),
+                value: "3".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        db.put_observations("SP500", &rows).await.unwrap();
+        db.put_observations("GDP", &rows[..1]).await.unwrap();
+        let series = db.list_cached_series().await.unwrap();
+        let spans: Vec<_> = series
+            .iter()
+            .map(|s| {
+                (
+                    s.series_id.as_str(),
+                    s.observation_start.to_string(),
+                    s.observation_end.to_string(),
+                    s.count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("GDP", "2023-01-03".to_string(), "2023-01-03".to_string(), 1),
+                (
+                    "SP500",
+                    "2023-01-03".to_string(),
+                    "2023-01-05".to_string(),
+                    3
+                ),
+            ]
+        );
+        assert!(series.iter().all(|s| s.age_secs.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_deletes_one_series() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        let rows: Vec<_> = ["2023-01-03", "2023-01-04"]
+            .into_iter()
+            .map(|date| RealtimeObservation {
+                date: date.parse().unwrap(),
+                value: "3".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        db.put_observations("SP500", &rows).await.unwrap();
+        db.put_observations("GDP", &rows).await.unwrap();
+        db.put_series(&FredEconomicDataSeries {
+            id: "SP500".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(db.delete_series_observations("SP500").await.unwrap(), 3);
+        assert!(db
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db.get_series("SP500", None).await.unwrap().is_none());
+        assert_eq!(db.count_observations("GDP").await.unwrap(), 2);
+        assert_eq!(db.delete_series_observations("SP500").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_forgets_empty_ranges_when_series_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = RealtimeObservationsDatabase::new(&dir.path().join("cache.db"))
+            .await
+            .unwrap();
+        db.create_tables().await.unwrap();
+        let date = |date: &str| Some(date.parse::<NaiveDate>().unwrap());
+        let since = Utc::now() - Duration::hours(1);
+        let series = FredEconomicDataSeries {
+            id: "SP500".to_string(),
+            last_updated: "2023-09-18T23:10:56Z".parse().unwrap(),
+            ..Default::default()
+        };
+        db.put_series(&series).await.unwrap();
+        db.record_empty_range("SP500", date("1950-01-01"), date("1959-12-31"), since)
+            .await
+            .unwrap();
+        assert!(db
+            .is_range_known_empty("SP500", date("1950-06-01"), date("1950-06-30"), since)
+            .await
+            .unwrap());
+        for (start, end) in [
+            (date("1949-12-31"), date("1950-06-30")),
+            (date("1950-06-01"), None),
+            (None, date("1950-06-30")),
+        ] {
+            assert!(!db
+                .is_range_known_empty("SP500", start, end, since)
+                .await
+                .unwrap());
+        }
+        assert!(!db
+            .is_range_known_empty("GDP", date("1950-06-01"), date("1950-06-30"), since)
+            .await
+            .unwrap());
+        assert!(!db
+            .is_range_known_empty(
+                "SP500",
+                date("1950-06-01"),
+                date("1950-06-30"),
+                Utc::now() + Duration::hours(1)
+            )
+            .await
+            .unwrap());
+        // the same last_updated keeps the range, a later one drops it
+        db.put_series(&series).await.unwrap();
+        assert!(db
+            .is_range_known_empty("SP500", date("1950-06-01"), date("1950-06-30"), since)
+            .await
+            .unwrap());
+        db.put_series(&FredEconomicDataSeries {
+            last_updated: "2023-09-19T23:10:56Z".parse().unwrap(),
+            ..series
+        })
+        .await
+        .unwrap();
+        assert!(!db
+            .is_range_known_empty("SP500", date("1950-06-01"), date("1950-06-30"), since)
+            .await
+            .unwrap());
+        // as do observations within it
+        db.record_empty_range("SP500", None, date("1959-12-31"), since)
+            .await
+            .unwrap();
+        assert!(db
+            .is_range_known_empty("SP500", None, date("1950-06-30"), since)
+            .await
+            .unwrap());
+        db.put_observations(
+            "SP500",
+            &[RealtimeObservation {
+                date: "1955-01-03".parse().unwrap(),
+                value: "36.75".to_string(),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap();
+        assert!(!db
+            .is_range_known_empty("SP500", None, date("1950-06-30"), since)
+            .await
+            .unwrap());
+        // ranges recorded before the cutoff are pruned as others are recorded
+        db.record_empty_range("SP500", date("1940-01-01"), date("1940-12-31"), since)
+            .await
+            .unwrap();
+        db.record_empty_range(
+            "SP500",
+            date("1930-01-01"),
+            date("1930-12-31"),
+            Utc::now() + Duration::hours(1),
+        )
+        .await
+        .unwrap();
+        let ranges: i64 = sqlx::query_scalar("select count(*) from series_coverage")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ranges, 1);
+    }
+}
@@ -1,31 +1,290 @@
+mod access_log;
+mod backfill;
+mod eviction;
+mod grpc;
+mod inspect;
+mod prometheus;
+mod upstream_status;
+
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
 use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Instant;
 
 use axum::{
-    extract::{Query, State},
-    response::Redirect,
-    routing::get,
+    error_handling::HandleErrorLayer,
+    extract::{Path, Query, RawQuery, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
     Json, Router,
 };
-use clap::Parser;
+use chrono::NaiveDate;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use futures::FutureExt;
 use hyper::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
 use tower_http::{
     compression::CompressionLayer,
     cors::{Any, CorsLayer},
+    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    LatencyUnit,
 };
 
 use stlouisfed_fred_web_proxy::{
+    csv_output::observations_to_csv,
     entities::{
-        FredEconomicDataSeries, GetObservationsParams, GetSeriesParams, RealtimeObservation,
+        AggregateSeriesTagsParams, AggregateSeriesTagsQuery, AggregatedTag, AggregatedTags,
+        CacheDiff, CacheSize, CachedSeries, ClearCacheParams, DateRange, DuplicatePolicy,
+        FormattedObservations, FredCategory, FredEconomicDataSeries, FredReleaseDate,
+        FredResponseTags, FredTransformation, Frequency, GetCategoryParams, GetDiffParams,
+        GetObservationsAtParams, GetObservationsParams, GetReleaseDatesParams,
+        GetSeriesOverviewParams, GetSeriesParams, GetSeriesSearchParams,
+        GetSeriesSearchRelatedTagsParams, GetTagsSeriesParams, Health, ObservationAt,
+        ObservationSummary, ObservationsByDate, ObservationsEnvelope, ObservationsFormat,
+        ObservationsMeta, ObservationsSource, OnError, RealtimeObservation, SelfTestResult,
+        SeriesDeletion, SeriesFetchError, SeriesOverview, SortOrder, TagMatch, UpstreamStats,
+        UpstreamStatus, WarmCacheParams, WarmCacheResponse,
+    },
+    fred::{
+        is_well_formed_api_key, is_well_formed_series_id, request_category_from_fred,
+        request_latest_observations_from_fred, request_observations_from_fred,
+        request_raw_observations_from_fred, request_raw_series_from_fred,
+        request_release_dates_from_fred, request_series_from_fred, request_series_search_from_fred,
+        request_series_search_related_tags_from_fred, request_series_tags_from_fred,
+        request_tags_series_from_fred, FredApiError, FredClient, FredObservations, FRED_BASE_URL,
     },
-    fred::{request_observations_from_fred, request_series_from_fred, FredApiError},
     local_cache::RealtimeObservationsDatabase,
+    response_cache::{PrecompressedResponse, PrecompressedResponses},
+    server_timing::ServerTiming,
+    transform::{apply_transform, rebase, resample, round_values},
+    validation::validate_observations,
 };
 
 #[derive(Clone)]
 struct AppState {
-    client: reqwest::Client,
-    fred_api_key: String,
+    fred: FredClient,
     realtime_observations_db: RealtimeObservationsDatabase,
+    cache_ttl_secs: Option<u64>,
+    max_staleness_secs: Option<u64>,
+    precompressed_responses: PrecompressedResponses,
+    min_observations_to_cache: usize,
+    /// Whether requests may read and write the local cache
+    use_cache: bool,
+    /// Series that are always fetched from FRED and never cached
+    no_cache_series: Arc<HashSet<String>>,
+    /// The only series served, if restricted
+    series_allowlist: Option<Arc<HashSet<String>>>,
+    admin_token: Option<String>,
+    /// Hosts admins may direct requests at instead of FRED, which are sent its API key
+    base_url_override_hosts: Arc<HashSet<String>>,
+    /// Most series one batch request may ask about
+    max_series_per_request: usize,
+    series_fetch_policy: SeriesFetchPolicy,
+    /// Cache observations as of past realtime periods, apart from the current observations
+    vintage_cache: bool,
+    /// How long to reuse the results of a FRED search; not at all when unset
+    search_cache_secs: Option<u64>,
+    /// Remember the last error fetching each series from FRED, for `/v0/cache/errors`
+    record_fetch_errors: bool,
+    /// Whether FRED answered when last checked
+    upstream_status: upstream_status::UpstreamStatusCache,
+    /// How far the cached observations may end before `observation_end` and still count as
+    /// complete, since recent observations are published with a lag
+    right_edge_grace_days: u32,
+    /// Record when each series was last requested, for `--evict-after-days`
+    track_access: bool,
+}
+
+/// When `/v0/series` asks FRED for a series' metadata rather than serving it from the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SeriesFetchPolicy {
+    /// Always ask FRED, refreshing the cached copy
+    Always,
+    /// Serve the cached copy while it is younger than `--cache-ttl-secs`
+    CacheFirst,
+    /// Never ask FRED; series that aren't cached are not found
+    CacheOnly,
+}
+
+/// Oldest TLS version to accept when connecting to FRED
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MinTlsVersion {
+    #[value(name = "1.2")]
+    Tls12,
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+/// Request header an admin can send to direct a single request at another FRED host
+const FRED_BASE_URL_HEADER: &str = "x-fred-base-url";
+
+/// Largest `tail` FRED will return in a single response
+const MAX_TAIL: usize = 100_000;
+
+/// Most holes among cached observations to fetch one by one, before refetching the whole range
+const MAX_HOLES: usize = 10;
+
+/// Largest `page_size` of observations
+const MAX_PAGE_SIZE: usize = 100_000;
+
+/// Most decimal places `round` can ask for; an `f64` holds no more than this reliably
+const MAX_ROUND: usize = 15;
+
+/// How long to trust FRED's word that a series, or a range of its dates, has no observations
+/// yet when `--cache-ttl-secs` is unset. A new series gains observations eventually, so this
+/// can't be forever.
+const EMPTY_SERIES_RECHECK_SECS: u64 = 3600;
+
+/// Response headers summarizing the numeric values of the observations returned
+const OBS_MIN_HEADER: &str = "x-obs-min";
+const OBS_MAX_HEADER: &str = "x-obs-max";
+const OBS_LAST_HEADER: &str = "x-obs-last";
+const OBS_COUNT_HEADER: &str = "x-obs-count";
+
+/// Response header with how many observations `limit` and `offset` chose a window from
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Largest `limit` of observations
+const MAX_LIMIT: usize = 100_000;
+
+/// Response header telling where the data came from: the local cache, FRED, or both
+const DATA_SOURCE_HEADER: &str = "x-data-source";
+
+/// Response header marking an observation from before the requested range (`fallback_to_latest`)
+const OUTSIDE_RANGE_HEADER: &str = "x-outside-requested-range";
+
+/// Response header marking a series that exists but has no observations yet
+const SERIES_EMPTY_HEADER: &str = "x-series-empty";
+
+/// Most dates one `/v0/observations/at` request may ask for
+const MAX_DATES: usize = 1000;
+
+/// Most tags one `/v0/tags/series` request may name, as `tag_match=any` asks FRED once per tag
+const MAX_TAG_NAMES: usize = 10;
+
+impl AppState {
+    /// Whether the request carries the admin bearer token configured with `--admin-token`.
+    fn is_admin(&self, headers: &HeaderMap) -> bool {
+        let Some(ref admin_token) = self.admin_token else {
+            return false;
+        };
+        let Some(token) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        // compare in constant time so the token can't be guessed byte by byte
+        token.len() == admin_token.len()
+            && token
+                .bytes()
+                .zip(admin_token.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
+    /// Refuse requests without the admin token.
+    fn require_admin(&self, headers: &HeaderMap) -> Result<(), FredApiError> {
+        if self.is_admin(headers) {
+            return Ok(());
+        }
+        Err(FredApiError {
+            status_code: StatusCode::UNAUTHORIZED,
+            error_message: Some("this requires the admin token".to_string()),
+        })
+    }
+
+    /// The state to serve a request with. When an admin sends `X-Fred-Base-Url` naming a host
+    /// in `--allow-base-url-override`, FRED calls go to that host instead and the cache is
+    /// neither read nor written, so a staging mirror can't pollute it. Other hosts are refused,
+    /// as they would be sent the API key.
+    fn for_request(&self, headers: &HeaderMap) -> Result<AppState, FredApiError> {
+        let Some(base_url) = headers.get(FRED_BASE_URL_HEADER) else {
+            return Ok(self.clone());
+        };
+        if self.base_url_override_hosts.is_empty() {
+            return Err(FredApiError {
+                status_code: StatusCode::FORBIDDEN,
+                error_message: Some(
+                    "overriding the FRED base URL is disabled on this server".to_string(),
+                ),
+            });
+        }
+        if !self.is_admin(headers) {
+            return Err(FredApiError {
+                status_code: StatusCode::UNAUTHORIZED,
+                error_message: Some(
+                    "overriding the FRED base URL requires the admin token".to_string(),
+                ),
+            });
+        }
+        let base_url = base_url
+            .to_str()
+            .ok()
+            .and_then(|base_url| reqwest::Url::parse(base_url).ok())
+            .ok_or(FredApiError {
+                status_code: StatusCode::BAD_REQUEST,
+                error_message: Some(format!("invalid {FRED_BASE_URL_HEADER} header")),
+            })?;
+        if !base_url
+            .host_str()
+            .is_some_and(|host| self.base_url_override_hosts.contains(host))
+        {
+            return Err(FredApiError {
+                status_code: StatusCode::FORBIDDEN,
+                error_message: Some(format!(
+                    "{FRED_BASE_URL_HEADER} must name a host in --allow-base-url-override"
+                )),
+            });
+        }
+        let mut app_state = self.clone();
+        app_state.fred = app_state.fred.with_base_url(base_url)?;
+        app_state.use_cache = false;
+        Ok(app_state)
+    }
+
+    /// Refuse series outside `--series-allowlist`, before anything is fetched for them.
+    fn check_series_allowed(&self, series_id: &str) -> Result<(), FredApiError> {
+        match &self.series_allowlist {
+            Some(allowlist) if !allowlist.contains(series_id) => Err(FredApiError {
+                status_code: StatusCode::FORBIDDEN,
+                error_message: Some(format!("{series_id} is not served by this server")),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether cached observations ending on `last_cached` cover a request through
+    /// `observation_end`, allowing for `--right-edge-grace-days` of publication lag.
+    fn covers_through(&self, last_cached: NaiveDate, observation_end: NaiveDate) -> bool {
+        let grace = chrono::Days::new(self.right_edge_grace_days.into());
+        observation_end
+            <= last_cached
+                .checked_add_days(grace)
+                .unwrap_or(NaiveDate::MAX)
+    }
+
+    /// Whether requests for `series_id` may read and write the local cache.
+    fn caches_series(&self, series_id: &str) -> bool {
+        self.use_cache && !self.no_cache_series.contains(series_id)
+    }
+
+    /// How old cached observations may be for a request, honoring the request's own
+    /// `max_staleness_secs` but never exceeding the server's `--max-staleness-secs`.
+    /// `None` means cached observations never go stale.
+    fn effective_max_staleness(&self, requested_secs: Option<u64>) -> Option<u64> {
+        match (
+            requested_secs.or(self.cache_ttl_secs),
+            self.max_staleness_secs,
+        ) {
+            (Some(requested), Some(max)) => Some(requested.min(max)),
+            (requested, max) => requested.or(max),
+        }
+    }
 }
 
 // type SharedAppState = std::sync::Arc<std::sync::RwLock<AppState>>;
@@ -51,146 +310,5459 @@ struct CommandLineInterface {
     #[arg(long, value_name = "FILE", env = "FRED_OBSERVATIONS_DB")]
     sqlite_db: std::path::PathBuf,
 
-    /// Free API key from https://fred.stlouisfed.org
-    #[arg(short, long, env = "FRED_API_KEY")]
-    fred_api_key: String,
+    /// Free API key from https://fred.stlouisfed.org. Give several (repeated, or separated by
+    /// commas) to take turns among them, raising the request rate FRED allows.
+    #[arg(
+        short,
+        long,
+        env = "FRED_API_KEY",
+        value_delimiter = ',',
+        required = true
+    )]
+    fred_api_key: Vec<String>,
+
+    /// Default age in seconds after which cached observations are refetched from FRED.
+    /// Requests may override it with `max_staleness_secs`. Unset means cached data never expires.
+    #[arg(long, value_name = "SECONDS", env = "FRED_CACHE_TTL_SECS")]
+    cache_ttl_secs: Option<u64>,
+
+    /// Upper bound on the staleness a request may ask for through `max_staleness_secs`
+    #[arg(long, value_name = "SECONDS", env = "FRED_MAX_STALENESS_SECS")]
+    max_staleness_secs: Option<u64>,
+
+    /// Reuse the results of an identical FRED search (e.g. `/v0/series/search/related_tags`)
+    /// for this many seconds instead of searching again. Unset means searches aren't cached.
+    #[arg(long, value_name = "SECONDS", env = "FRED_SEARCH_CACHE_SECS")]
+    search_cache_secs: Option<u64>,
+
+    /// Number of gzip-compressed `/v0/observations` responses served from cache to keep in memory
+    /// and reuse verbatim for identical requests. 0 disables it.
+    #[arg(long, value_name = "ENTRIES", default_value_t = 0)]
+    precompressed_cache_entries: usize,
+
+    /// Only cache observations of a series with more than this many, by FRED's count of the whole
+    /// series. Smaller series are fetched from FRED on every request instead, which keeps the
+    /// cache focused on large series at the cost of a FRED call per request for small ones.
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    min_observations_to_cache: usize,
+
+    /// Serve a request from the cache without asking FRED for newer observations when the cached
+    /// ones end at most this many days before its `observation_end`. FRED publishes with a lag,
+    /// so asking through today would otherwise call FRED on every request. Newer observations
+    /// then arrive once the cache goes stale (--cache-ttl-secs).
+    #[arg(long, value_name = "DAYS", default_value_t = 0)]
+    right_edge_grace_days: u32,
+
+    /// Which observation to keep when there are several for one date: of the rows FRED returns
+    /// from different realtime periods, and of a cached observation and a refetched one
+    #[arg(long, value_enum, default_value_t = DuplicatePolicy::LatestRealtime)]
+    duplicate_policy: DuplicatePolicy,
+
+    /// Also store cached values as numbers (SQLite REAL) next to FRED's text, for aggregating
+    /// in SQL without casts. Missing and other non-numeric values are stored as null.
+    #[arg(long)]
+    numeric_values: bool,
+
+    /// Oldest TLS version to accept when connecting to FRED (1.2 or 1.3). 1.3 connects through
+    /// rustls, whose built-in root certificates then stand in for the system's.
+    #[arg(long, value_enum, value_name = "VERSION", default_value = "1.2")]
+    upstream_min_tls_version: MinTlsVersion,
+
+    /// Where FRED's API is served. Point it at a mirror, a corporate proxy, or a mock server.
+    #[arg(long, value_name = "URL", env = "FRED_BASE_URL", default_value = FRED_BASE_URL)]
+    fred_base_url: reqwest::Url,
+
+    /// Most requests to send FRED per minute with each API key. Requests beyond it wait.
+    #[arg(long, value_name = "REQUESTS_PER_MINUTE", default_value_t = 120)]
+    fred_rate_limit: u32,
+
+    /// How many times to retry a FRED request that failed with status 429 or 5xx (other than
+    /// 502, which failing to connect or to decode the response are reported as) or timed out,
+    /// with exponential backoff and jitter
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    upstream_max_retries: u32,
+
+    /// Delay before the first retry of a FRED request, doubling with each retry after
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 500)]
+    upstream_retry_base_delay_ms: u64,
+
+    /// How long to reuse a check of whether FRED is reachable for `/v0/upstream/status`, so
+    /// that polling it doesn't spend FRED quota
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    upstream_status_ttl_secs: u64,
+
+    /// Record the last error fetching each series from FRED in the database, listed by
+    /// `/v0/cache/errors`
+    #[arg(long)]
+    record_fetch_errors: bool,
+
+    /// Delete cached observations of series not requested in this many days. Disabled when unset.
+    #[arg(long, value_name = "DAYS")]
+    evict_after_days: Option<u64>,
+
+    /// How often to look for series to evict with `--evict-after-days`
+    #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+    eviction_interval_secs: u64,
+
+    /// Switch the database to incremental auto-vacuum and return its free pages to the
+    /// filesystem this often. Disabled when unset.
+    #[arg(long, value_name = "SECONDS")]
+    incremental_vacuum_interval_secs: Option<u64>,
+
+    /// Series to always fetch from FRED and never cache, e.g. ones revised constantly.
+    /// Comma-separated.
+    #[arg(long, value_name = "SERIES_IDS", value_delimiter = ',')]
+    no_cache_series: Vec<String>,
+
+    /// Serve only these series, refusing requests for others with status 403. Comma-separated.
+    /// All series are served when neither this nor --series-allowlist-file is given.
+    #[arg(long, value_name = "SERIES_IDS", value_delimiter = ',')]
+    series_allowlist: Vec<String>,
+
+    /// Like --series-allowlist, reading the series from a file with one per line. Blank lines and
+    /// lines starting with `#` are ignored.
+    #[arg(long, value_name = "FILE")]
+    series_allowlist_file: Option<std::path::PathBuf>,
+
+    /// Port for an additional gRPC server exposing the same lookups. Disabled when unset.
+    #[arg(long)]
+    grpc_port: Option<u16>,
+
+    /// Secret that authorizes administrative requests, sent as `Authorization: Bearer <token>`
+    #[arg(long, env = "FRED_PROXY_ADMIN_TOKEN")]
+    admin_token: Option<String>,
+
+    /// Let admins point a single request at one of these hosts instead of FRED with the
+    /// `X-Fred-Base-Url` header. They are sent the API key. Comma-separated.
+    #[arg(long, value_name = "HOSTS", value_delimiter = ',')]
+    allow_base_url_override: Vec<String>,
+
+    /// Most series a single batch request (e.g. `/v0/series/tags/aggregate`) may ask about.
+    /// Each series costs a FRED call, so this bounds the work one request can cause.
+    #[arg(long, value_name = "COUNT", default_value_t = 25)]
+    max_series_per_request: usize,
+
+    /// When `/v0/series` fetches metadata from FRED instead of serving it from the cache
+    #[arg(long, value_enum, default_value_t = SeriesFetchPolicy::Always)]
+    series_fetch_policy: SeriesFetchPolicy,
+
+    /// Cache the observations of requests for a realtime period wholly in the past, which won't
+    /// change, apart from the cache of current observations
+    #[arg(long)]
+    vintage_cache: bool,
+
+    /// Most requests to handle at once. Requests beyond it are refused with status 503 rather than
+    /// queued. Unlimited when unset.
+    #[arg(long, value_name = "COUNT")]
+    max_concurrent_requests: Option<usize>,
+
+    /// Label the observations this deployment caches with where they came from, when several
+    /// deployments share one database
+    #[arg(long, value_name = "LABEL", env = "FRED_PROXY_CACHE_SOURCE")]
+    cache_source: Option<String>,
+
+    /// Cache observations apart under this deployment's --cache-source (or, without it, a hash of
+    /// its FRED API key), never serving nor overwriting those another deployment cached
+    #[arg(long)]
+    partition_cache_by_source: bool,
+
+    /// Append a JSON object per request (ndjson) to this file, or to stdout if it is `-`
+    #[arg(long, value_name = "FILE", env = "FRED_PROXY_ACCESS_LOG")]
+    access_log: Option<std::path::PathBuf>,
+
+    /// Validate the configuration and exit without starting anything
+    #[arg(long)]
+    check_config: bool,
+
+    /// Run a one-shot job instead of the server
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl CommandLineInterface {
+    /// Check the configuration up front, so that mistakes are reported clearly at startup
+    /// rather than surfacing as errors on the first request.
+    fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::<String>::new();
+        if !self
+            .fred_api_key
+            .iter()
+            .all(|key| is_well_formed_api_key(key))
+        {
+            problems.push("--fred-api-key should be 32 lowercase letters and digits".to_string());
+        }
+        if let Err(e) = check_writable(&self.sqlite_db) {
+            problems.push(format!(
+                "--sqlite-db {} is not writable: {e}",
+                self.sqlite_db.display()
+            ));
+        }
+        if self.cache_ttl_secs == Some(0) {
+            problems.push("--cache-ttl-secs must be greater than 0".to_string());
+        }
+        if self.max_staleness_secs == Some(0) {
+            problems.push("--max-staleness-secs must be greater than 0".to_string());
+        }
+        if let (Some(ttl), Some(max)) = (self.cache_ttl_secs, self.max_staleness_secs) {
+            if ttl > max {
+                problems.push(format!(
+                    "--cache-ttl-secs ({ttl}) must not exceed --max-staleness-secs ({max})"
+                ));
+            }
+        }
+        if self.evict_after_days == Some(0) {
+            problems.push("--evict-after-days must be greater than 0".to_string());
+        }
+        if self.eviction_interval_secs == 0 {
+            problems.push("--eviction-interval-secs must be greater than 0".to_string());
+        }
+        if !matches!(self.fred_base_url.scheme(), "http" | "https")
+            || self.fred_base_url.cannot_be_a_base()
+        {
+            problems.push("--fred-base-url must be an http or https URL".to_string());
+        }
+        if self.fred_rate_limit == 0 {
+            problems.push("--fred-rate-limit must be greater than 0".to_string());
+        }
+        if self.incremental_vacuum_interval_secs == Some(0) {
+            problems.push("--incremental-vacuum-interval-secs must be greater than 0".to_string());
+        }
+        problems.extend(
+            self.no_cache_series
+                .iter()
+                .filter(|series_id| !is_well_formed_series_id(series_id))
+                .map(|series_id| {
+                    format!("--no-cache-series {series_id:?} is not a valid FRED series ID")
+                }),
+        );
+        problems.extend(
+            self.series_allowlist
+                .iter()
+                .filter(|series_id| !is_well_formed_series_id(series_id))
+                .map(|series_id| {
+                    format!("--series-allowlist {series_id:?} is not a valid FRED series ID")
+                }),
+        );
+        if matches!(&self.series_allowlist_file, Some(file) if !file.is_file()) {
+            problems.push("--series-allowlist-file must be an existing file".to_string());
+        }
+        if self.max_series_per_request == 0 {
+            problems.push("--max-series-per-request must be greater than 0".to_string());
+        }
+        if self.max_concurrent_requests == Some(0) {
+            problems.push("--max-concurrent-requests must be greater than 0".to_string());
+        }
+        if !self.allow_base_url_override.is_empty() && self.admin_token.is_none() {
+            problems.push("--allow-base-url-override requires --admin-token".to_string());
+        }
+        if self.grpc_port == Some(self.port) {
+            problems.push("--grpc-port must differ from --port".to_string());
+        }
+        match &self.command {
+            Some(Command::Backfill(args)) => problems.extend(args.validate()),
+            Some(Command::Inspect(args)) => problems.extend(args.validate()),
+            None => {}
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+}
+
+/// Refuse requests with status 503 while `max` are already being handled, so that a load spike
+/// is turned away quickly instead of piling up into timeouts.
+fn with_load_shedding(app: Router, max: usize) -> Router {
+    app.layer(
+        tower::ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|e: tower::BoxError| async move {
+                if e.is::<tower::load_shed::error::Overloaded>() {
+                    FredApiError {
+                        status_code: StatusCode::SERVICE_UNAVAILABLE,
+                        error_message: Some("too many requests at once; try again".to_string()),
+                    }
+                } else {
+                    FredApiError::default()
+                }
+            }))
+            .load_shed()
+            // global, because the router applies the layer to each route separately
+            .layer(tower::limit::GlobalConcurrencyLimitLayer::new(max)),
+    )
+}
+
+/// The series named by `--series-allowlist` and `--series-allowlist-file` together, or `None` if
+/// neither restricts them.
+fn series_allowlist(
+    series_ids: &[String],
+    file: Option<&std::path::Path>,
+) -> std::io::Result<Option<Arc<HashSet<String>>>> {
+    let mut allowlist: HashSet<String> = series_ids.iter().cloned().collect();
+    if let Some(file) = file {
+        allowlist.extend(
+            std::fs::read_to_string(file)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+    } else if series_ids.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(allowlist)))
+}
+
+/// Cache source label identifying a FRED API key without revealing it. With several keys, the
+/// first one identifies them.
+fn api_key_source(api_key: &str) -> String {
+    let digest = Sha256::digest(api_key.as_bytes());
+    let hex: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+    format!("key:{hex}")
+}
+
+/// Client for FRED as configured on the command line
+fn fred_client(cli: &CommandLineInterface) -> Result<FredClient, Box<dyn std::error::Error>> {
+    Ok(FredClient::new(
+        upstream_http_client(cli.upstream_min_tls_version)?,
+        cli.fred_api_key[0].clone(),
+    )
+    .with_base_url(cli.fred_base_url.clone())?
+    .with_api_keys(cli.fred_api_key.clone())
+    .with_duplicate_policy(cli.duplicate_policy)
+    .with_rate_limit(cli.fred_rate_limit)
+    .with_retries(
+        cli.upstream_max_retries,
+        std::time::Duration::from_millis(cli.upstream_retry_base_delay_ms),
+    ))
+}
+
+/// HTTP client for calling FRED, refusing TLS versions older than `min_tls_version`. The system's
+/// TLS library can't be limited to 1.3, so 1.3 is left to rustls.
+fn upstream_http_client(min_tls_version: MinTlsVersion) -> reqwest::Result<reqwest::Client> {
+    let builder = reqwest::Client::builder();
+    match min_tls_version {
+        MinTlsVersion::Tls12 => builder.min_tls_version(reqwest::tls::Version::TLS_1_2),
+        MinTlsVersion::Tls13 => builder
+            .use_rustls_tls()
+            .min_tls_version(reqwest::tls::Version::TLS_1_3),
+    }
+    .build()
+}
+
+/// Resolves on the first SIGINT or SIGTERM. The handlers are installed before this returns, so
+/// a signal arriving before the future is first polled still stops the server.
+fn shutdown_signal() -> std::io::Result<impl Future<Output = ()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    Ok(async move {
+        let name = tokio::select! {
+            _ = interrupt.recv() => "SIGINT",
+            _ = terminate.recv() => "SIGTERM",
+        };
+        tracing::info!(
+            signal = name,
+            "shutting down after in-flight requests finish"
+        );
+    })
+}
+
+/// Check that the database file can be written, or created if it doesn't exist yet.
+fn check_writable(path: &std::path::Path) -> std::io::Result<()> {
+    if path.exists() {
+        return std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map(|_| ());
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    if std::fs::metadata(parent)?.permissions().readonly() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("directory {} is read-only", parent.display()),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch observations of the given series into the local cache, then exit
+    Backfill(backfill::BackfillArgs),
+    /// Print a summary of a series and its latest observations as FRED has them, then exit
+    Inspect(inspect::InspectArgs),
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
     let cli = CommandLineInterface::parse();
-    let client = reqwest::Client::new();
+    if let Err(problems) = cli.validate() {
+        CommandLineInterface::command()
+            .error(clap::error::ErrorKind::ValueValidation, problems)
+            .exit();
+    }
+    if cli.check_config {
+        println!("configuration OK");
+        return Ok(());
+    }
+    let fred = fred_client(&cli)?;
+    let cache_source = cli.cache_source.or_else(|| {
+        cli.partition_cache_by_source
+            .then(|| api_key_source(&cli.fred_api_key[0]))
+    });
+    if let Some(Command::Inspect(args)) = cli.command {
+        return inspect::run(&fred, args).await;
+    }
     let port = cli.port;
+    let realtime_observations_db = RealtimeObservationsDatabase::new(&cli.sqlite_db)
+        .await?
+        .with_source(cache_source, cli.partition_cache_by_source)
+        .with_duplicate_policy(cli.duplicate_policy)
+        .with_numeric_values(cli.numeric_values);
+    realtime_observations_db.create_tables().await?;
+    if cli.incremental_vacuum_interval_secs.is_some() {
+        realtime_observations_db.enable_incremental_vacuum().await?;
+    }
+    if let Some(Command::Backfill(args)) = cli.command {
+        return backfill::run(&fred, &realtime_observations_db, args).await;
+    }
+    let series_allowlist =
+        series_allowlist(&cli.series_allowlist, cli.series_allowlist_file.as_deref())?;
     let app_state = AppState {
-        client,
-        fred_api_key: cli.fred_api_key,
-        realtime_observations_db: RealtimeObservationsDatabase::new(&cli.sqlite_db).await?,
+        fred,
+        realtime_observations_db,
+        cache_ttl_secs: cli.cache_ttl_secs,
+        max_staleness_secs: cli.max_staleness_secs,
+        precompressed_responses: PrecompressedResponses::new(cli.precompressed_cache_entries),
+        min_observations_to_cache: cli.min_observations_to_cache,
+        use_cache: true,
+        no_cache_series: Arc::new(cli.no_cache_series.into_iter().collect()),
+        series_allowlist,
+        admin_token: cli.admin_token,
+        base_url_override_hosts: Arc::new(
+            cli.allow_base_url_override
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+        ),
+        max_series_per_request: cli.max_series_per_request,
+        series_fetch_policy: cli.series_fetch_policy,
+        vintage_cache: cli.vintage_cache,
+        search_cache_secs: cli.search_cache_secs,
+        right_edge_grace_days: cli.right_edge_grace_days,
+        record_fetch_errors: cli.record_fetch_errors,
+        upstream_status: upstream_status::UpstreamStatusCache::new(std::time::Duration::from_secs(
+            cli.upstream_status_ttl_secs,
+        )),
+        track_access: cli.evict_after_days.is_some(),
     };
-    app_state.realtime_observations_db.create_tables().await?;
+    if let Some(evict_after_days) = cli.evict_after_days {
+        tokio::spawn(eviction::run(
+            app_state.clone(),
+            evict_after_days,
+            std::time::Duration::from_secs(cli.eviction_interval_secs),
+        ));
+    }
+    if let Some(interval_secs) = cli.incremental_vacuum_interval_secs {
+        tokio::spawn(eviction::run_incremental_vacuum(
+            app_state.realtime_observations_db.clone(),
+            std::time::Duration::from_secs(interval_secs),
+        ));
+    }
+    let metrics = prometheus::install()?;
+    let access_log = cli
+        .access_log
+        .as_deref()
+        .map(access_log::AccessLog::open)
+        .transpose()?;
     let app = Router::new()
         .route("/v0/observations", get(get_observations_handler))
+        .route("/v0/observations/at", post(post_observations_at_handler))
         .route("/v0/series", get(get_series_handler))
+        .route("/v0/series/overview", get(get_series_overview_handler))
+        .route("/v0/series/search", get(get_series_search_handler))
+        .route("/v0/release/dates", get(get_release_dates_handler))
+        .route("/v0/categories", get(get_category_handler))
+        .route(
+            "/v0/series/search/related_tags",
+            get(get_series_search_related_tags_handler),
+        )
+        .route(
+            "/v0/series/tags/aggregate",
+            post(post_series_tags_aggregate_handler),
+        )
+        .route("/v0/tags/series", get(get_tags_series_handler))
+        .route("/v0/diff", get(get_diff_handler))
+        .route("/v0/cache", delete(delete_cache_handler))
+        .route("/v0/cache/warm", post(post_cache_warm_handler))
+        .route("/v0/cache/series", get(get_cached_series_handler))
+        .route(
+            "/v0/cache/series/:series_id",
+            delete(delete_cached_series_handler),
+        )
+        .route("/v0/cache/errors", get(get_fetch_errors_handler))
+        .route("/v0/cache/size", get(get_cache_size_handler))
+        .route("/v0/upstream/status", get(get_upstream_status_handler))
+        .route("/v0/selftest", post(post_self_test_handler))
+        .route("/healthz", get(get_health_handler))
+        .route("/metrics", get(prometheus::render).with_state(metrics))
         .route(
             "/",
             get(Redirect::temporary(
                 "https://github.com/proprietary/stlouisfed-fred-web-proxy",
             )),
         )
-        .layer(CorsLayer::new().allow_origin(Any))
-        .layer(CompressionLayer::new().gzip(true))
+        .route_layer(axum::middleware::from_fn(prometheus::record_latency))
         .with_state(app_state.clone());
+    let app = match cli.max_concurrent_requests {
+        Some(max) => with_load_shedding(app, max),
+        None => app,
+    };
+    let app = match access_log {
+        Some(access_log) => app.layer(axum::middleware::from_fn_with_state(
+            access_log,
+            access_log::record,
+        )),
+        None => app,
+    };
+    let app = app
+        .layer(CorsLayer::new().allow_origin(Any).expose_headers([
+            HeaderName::from_static(OBS_MIN_HEADER),
+            HeaderName::from_static(OBS_MAX_HEADER),
+            HeaderName::from_static(OBS_LAST_HEADER),
+            HeaderName::from_static(OBS_COUNT_HEADER),
+            HeaderName::from_static(TOTAL_COUNT_HEADER),
+            HeaderName::from_static(DATA_SOURCE_HEADER),
+            header::WARNING,
+            header::ETAG,
+        ]))
+        .layer(CompressionLayer::new().gzip(true))
+        // logs each request's method and path, then its status and latency
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::new().level(tracing::Level::INFO))
+                .on_response(
+                    DefaultOnResponse::new()
+                        .level(tracing::Level::INFO)
+                        .latency_unit(LatencyUnit::Millis),
+                ),
+        );
     let bind_addr: std::net::SocketAddr =
         std::net::SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
-    axum::Server::bind(&bind_addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    // both servers stop taking connections on the same signal, and finish the requests they have
+    let shutdown = shutdown_signal()?.shared();
+    let realtime_observations_db = app_state.realtime_observations_db.clone();
+    let http_server = async {
+        axum::Server::bind(&bind_addr)
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(shutdown.clone())
+            .await
+            .map_err(Box::<dyn std::error::Error>::from)
+    };
+    match cli.grpc_port {
+        Some(grpc_port) => {
+            let grpc_addr = std::net::SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), grpc_port);
+            let grpc_server = async {
+                tonic::transport::Server::builder()
+                    .add_service(grpc::FredProxyService::new(app_state))
+                    .serve_with_shutdown(grpc_addr, shutdown.clone())
+                    .await
+                    .map_err(Box::<dyn std::error::Error>::from)
+            };
+            tokio::try_join!(http_server, grpc_server)?;
+        }
+        None => http_server.await?,
+    }
+    // waits for background jobs still holding a connection, then checkpoints the WAL
+    realtime_observations_db.close().await;
     Ok(())
 }
 
 async fn get_series_handler(
     State(app_state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<GetSeriesParams>,
-) -> Result<Json<FredEconomicDataSeries>, FredApiError> {
-    let series_response = request_series_from_fred(
-        app_state.client.clone(),
-        &app_state.fred_api_key,
-        &params.series_id,
+) -> Result<Response, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    if params.raw {
+        app_state.check_series_allowed(&params.series_id)?;
+        let raw = noting_fetch_error(
+            &app_state,
+            &params.series_id,
+            request_raw_series_from_fred(&app_state.fred, &params.series_id).await,
+        )
+        .await?;
+        return Ok(with_data_source(
+            Json(raw).into_response(),
+            DataSource::Upstream,
+        ));
+    }
+    let (series, source) = fetch_series_with_source(&app_state, &params.series_id).await?;
+    let etag = series_etag(&series);
+    let response = if etag_matches(&headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(series).into_response()
+    };
+    let mut response = with_data_source(response, source);
+    response
+        .headers_mut()
+        .insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    Ok(response)
+}
+
+/// Entity tag of a series' metadata, which changes whenever FRED updates the series.
+fn series_etag(series: &FredEconomicDataSeries) -> String {
+    let digest = Sha256::new()
+        .chain_update(series.id.as_bytes())
+        .chain_update(series.last_updated.to_rfc3339().as_bytes())
+        .finalize();
+    let hex: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+    format!("\"{hex}\"")
+}
+
+/// Whether `If-None-Match` names `etag` (or is `*`). Tags are compared weakly, ignoring a
+/// `W/` prefix, as `If-None-Match` calls for.
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+async fn get_series_overview_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GetSeriesOverviewParams>,
+) -> Result<Response, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    let (metadata, metadata_source) =
+        fetch_series_with_source(&app_state, &params.series_id).await?;
+    let (latest, observation_count, observations_source) =
+        latest_observation(&app_state, &metadata).await?;
+    let source = match (metadata_source, observations_source) {
+        (DataSource::Cache, DataSource::Cache) => DataSource::Cache,
+        (DataSource::Cache, _) | (_, DataSource::Cache) => DataSource::Merged,
+        _ => DataSource::Upstream,
+    };
+    let overview = SeriesOverview {
+        first_date: metadata.observation_start,
+        metadata,
+        latest,
+        observation_count,
+    };
+    Ok(with_data_source(Json(overview).into_response(), source))
+}
+
+/// A series' latest observation and how many it has, from the cache when it holds every
+/// observation from the first through the last one in the series' metadata, and otherwise with
+/// a single request to FRED for just the latest.
+async fn latest_observation(
+    app_state: &AppState,
+    series: &FredEconomicDataSeries,
+) -> Result<(Option<RealtimeObservation>, u64, DataSource), FredApiError> {
+    let db = &app_state.realtime_observations_db;
+    if app_state.caches_series(&series.id) {
+        let params = GetObservationsParams {
+            series_id: series.id.clone(),
+            ..Default::default()
+        };
+        let last_cached = db
+            .get_latest_observations(&series.id, None, None, 1)
+            .await?
+            .pop();
+        // a window of the series is complete, but too few to count
+        let complete = !has_stale_observations(app_state, &params).await?
+            && db
+                .is_counted_from(&series.id, series.observation_start)
+                .await?
+            && !db.is_missing_observations(&series.id).await?;
+        if let Some(last) =
+            last_cached.filter(|last| complete && last.date >= series.observation_end)
+        {
+            let count = db.count_observations(&series.id).await?;
+            record_access(app_state, &series.id).await;
+            return Ok((Some(last), count, DataSource::Cache));
+        }
+    }
+    let mut fresh = fetch_from_fred(
+        app_state,
+        &ServerTiming::default(),
+        &series.id,
+        request_latest_observations_from_fred(
+            &app_state.fred,
+            &series.id,
+            None,
+            None,
+            None,
+            None,
+            FredTransformation::default(),
+            1,
+        ),
+    )
+    .await?;
+    Ok((
+        fresh.observations.pop(),
+        fresh.count as u64,
+        DataSource::Upstream,
+    ))
+}
+
+/// Fetch a series' metadata from FRED, recording it in the local cache, or serve it from the
+/// cache as `--series-fetch-policy` allows.
+async fn fetch_series(
+    app_state: &AppState,
+    series_id: &str,
+) -> Result<FredEconomicDataSeries, FredApiError> {
+    Ok(fetch_series_with_source(app_state, series_id).await?.0)
+}
+
+/// [`fetch_series`], also telling whether the series came from the cache or FRED
+async fn fetch_series_with_source(
+    app_state: &AppState,
+    series_id: &str,
+) -> Result<(FredEconomicDataSeries, DataSource), FredApiError> {
+    app_state.check_series_allowed(series_id)?;
+    let db = &app_state.realtime_observations_db;
+    match app_state.series_fetch_policy {
+        SeriesFetchPolicy::Always => {}
+        SeriesFetchPolicy::CacheFirst if app_state.caches_series(series_id) => {
+            let fetched_since = staleness_cutoff(app_state.effective_max_staleness(None));
+            if let Some(series) = db.get_series(series_id, fetched_since).await? {
+                return Ok((series, DataSource::Cache));
+            }
+        }
+        SeriesFetchPolicy::CacheFirst => {}
+        SeriesFetchPolicy::CacheOnly => {
+            let cached = match app_state.caches_series(series_id) {
+                true => db.get_series(series_id, None).await?,
+                false => None,
+            };
+            let cached = cached.ok_or(FredApiError {
+                status_code: StatusCode::NOT_FOUND,
+                error_message: Some(format!("{series_id} is not cached")),
+            })?;
+            return Ok((cached, DataSource::Cache));
+        }
+    }
+    let series_response = noting_fetch_error(
+        app_state,
+        series_id,
+        request_series_from_fred(&app_state.fred, series_id).await,
     )
     .await?;
     let series: FredEconomicDataSeries = series_response
         .seriess
-        .get(0)
+        .first()
         .ok_or(FredApiError {
             status_code: StatusCode::NOT_FOUND,
             error_message: None,
         })?
         .clone();
-    let maybe_stored_series = app_state
-        .realtime_observations_db
-        .get_series(&params.series_id)
-        .await
-        .map_err(|_| FredApiError::default())?;
-    match maybe_stored_series {
-        None => {
-            app_state
-                .realtime_observations_db
-                .put_series(&series.clone())
-                .await
-                .map_err(|_| FredApiError::default())?;
+    if app_state.caches_series(series_id) {
+        db.put_series(&series).await?;
+    }
+    Ok((series, DataSource::Upstream))
+}
+
+async fn get_release_dates_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GetReleaseDatesParams>,
+) -> Result<Json<Vec<FredReleaseDate>>, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    let release_dates = request_release_dates_from_fred(
+        &app_state.fred,
+        params.release_id,
+        params.include_release_dates_with_no_data,
+    )
+    .await?;
+    Ok(Json(release_dates))
+}
+
+async fn get_category_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GetCategoryParams>,
+) -> Result<Json<Vec<FredCategory>>, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    let categories = request_category_from_fred(&app_state.fred, params.category_id).await?;
+    Ok(Json(categories.categories))
+}
+
+async fn get_series_search_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GetSeriesSearchParams>,
+) -> Result<Response, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    if params.search_text.trim().is_empty() {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some("search_text is required".to_string()),
+        });
+    }
+    let key = format!(
+        "series/search?search_text={}&limit={:?}&order_by={}",
+        normalized_search_text(&params.search_text),
+        params.limit,
+        params.order_by.as_deref().unwrap_or_default(),
+    );
+    let (mut series, source) = cached_search(&app_state, &key, async {
+        Ok(request_series_search_from_fred(&app_state.fred, &params)
+            .await?
+            .seriess)
+    })
+    .await?;
+    series.retain(|series| app_state.check_series_allowed(&series.id).is_ok());
+    Ok(with_data_source(Json(series).into_response(), source))
+}
+
+async fn get_series_search_related_tags_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GetSeriesSearchRelatedTagsParams>,
+) -> Result<Response, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    if params.series_search_text.trim().is_empty() || params.tag_names.trim().is_empty() {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some("series_search_text and tag_names are required".to_string()),
+        });
+    }
+    let mut tag_names: Vec<&str> = params.tag_names.split(';').map(str::trim).collect();
+    tag_names.sort_unstable();
+    let key = format!(
+        "series/search/related_tags?series_search_text={}&tag_names={}&exclude_tag_names={}&limit={:?}&offset={:?}",
+        normalized_search_text(&params.series_search_text),
+        tag_names.join(";"),
+        params.exclude_tag_names.as_deref().unwrap_or_default(),
+        params.limit,
+        params.offset,
+    );
+    let (tags, source) = cached_search(
+        &app_state,
+        &key,
+        request_series_search_related_tags_from_fred(&app_state.fred, &params),
+    )
+    .await?;
+    Ok(with_data_source(Json(tags).into_response(), source))
+}
+
+/// Search text with case and spacing differences removed, so equivalent searches share a cache
+/// entry.
+fn normalized_search_text(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Serve the results of the FRED search identified by `key` from the search cache while they
+/// are younger than `--search-cache-secs`, or run `search` and cache its results.
+async fn cached_search<T>(
+    app_state: &AppState,
+    key: &str,
+    search: impl Future<Output = Result<T, FredApiError>>,
+) -> Result<(T, DataSource), FredApiError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let Some(secs) = app_state.search_cache_secs.filter(|_| app_state.use_cache) else {
+        return Ok((search.await?, DataSource::Upstream));
+    };
+    let db = &app_state.realtime_observations_db;
+    let cached_since =
+        staleness_cutoff(Some(secs)).unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC);
+    let cached = db.get_search_result(key, cached_since).await?;
+    if let Some(results) = cached.and_then(|json| serde_json::from_str(&json).ok()) {
+        return Ok((results, DataSource::Cache));
+    }
+    let results = search.await?;
+    let json = serde_json::to_string(&results).map_err(|_| FredApiError::default())?;
+    db.put_search_result(key, &json).await?;
+    Ok((results, DataSource::Upstream))
+}
+
+async fn get_tags_series_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GetTagsSeriesParams>,
+) -> Result<Json<Vec<FredEconomicDataSeries>>, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    let tag_names: Vec<&str> = params
+        .tag_names
+        .split(',')
+        .map(str::trim)
+        .filter(|tag_name| !tag_name.is_empty())
+        .collect();
+    if tag_names.is_empty() || tag_names.len() > MAX_TAG_NAMES {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!(
+                "tag_names must list between 1 and {MAX_TAG_NAMES} tags"
+            )),
+        });
+    }
+    let mut series = match params.tag_match {
+        TagMatch::All => {
+            request_tags_series_from_fred(&app_state.fred, &tag_names.join(";")).await?
         }
-        Some(stored_series) => {
-            if stored_series.last_updated < series.last_updated {
-                app_state
-                    .realtime_observations_db
-                    .put_series(&series.clone())
-                    .await
-                    .map_err(|_| FredApiError::default())?;
+        // FRED only matches all tags, so ask about each tag on its own and take the union
+        TagMatch::Any => {
+            let mut union = Vec::<FredEconomicDataSeries>::new();
+            for tag_name in tag_names {
+                for series in request_tags_series_from_fred(&app_state.fred, tag_name).await? {
+                    if !union.iter().any(|s| s.id == series.id) {
+                        union.push(series);
+                    }
+                }
             }
+            union
         }
-    }
+    };
+    series.retain(|series| app_state.check_series_allowed(&series.id).is_ok());
     Ok(Json(series))
 }
 
-async fn get_observations_handler(
+async fn post_series_tags_aggregate_handler(
     State(app_state): State<AppState>,
-    Query(params): Query<GetObservationsParams>,
-) -> Result<Json<Vec<RealtimeObservation>>, FredApiError> {
-    // if user requested realtime/"ALFRED" data, then do not use local cache
-    if params.realtime_start.is_some() || params.realtime_end.is_some() {
-        // bypass cache
-        // because not willing to cache different versions of the same data over and over
-        let fresh = request_observations_from_fred(
-            app_state.client.clone(),
-            &app_state.fred_api_key,
-            &params.series_id,
-            params.observation_start,
-            params.observation_end,
-            params.realtime_start,
-            params.realtime_end,
-        )
-        .await?;
-        return Ok(Json(fresh));
+    headers: HeaderMap,
+    Query(query): Query<AggregateSeriesTagsQuery>,
+    Json(params): Json<AggregateSeriesTagsParams>,
+) -> Result<(StatusCode, Json<AggregatedTags>), FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    check_series_count(&app_state, params.series_ids.len())?;
+    if let Some(series_id) = params
+        .series_ids
+        .iter()
+        .find(|series_id| !is_well_formed_series_id(series_id))
+    {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!("malformed series_id: {series_id}")),
+        });
+    }
+    for series_id in &params.series_ids {
+        app_state.check_series_allowed(series_id)?;
     }
-    let cached = app_state
+    let mut series_ids = params.series_ids;
+    series_ids.sort_unstable();
+    series_ids.dedup();
+    // one series at a time, to stay well clear of FRED's rate limit
+    let mut tag_lists = Vec::with_capacity(series_ids.len());
+    let mut errors = std::collections::BTreeMap::new();
+    for series_id in series_ids {
+        match request_series_tags_from_fred(&app_state.fred, &series_id).await {
+            Ok(tags) => tag_lists.push(tags),
+            Err(e) if query.on_error == OnError::Fail => return Err(e),
+            Err(e) => {
+                errors.insert(series_id, e.to_string());
+            }
+        }
+    }
+    let status = match errors.is_empty() {
+        true => StatusCode::OK,
+        false => StatusCode::MULTI_STATUS,
+    };
+    Ok((
+        status,
+        Json(AggregatedTags {
+            tags: aggregate_tags(&tag_lists),
+            errors,
+        }),
+    ))
+}
+
+/// Refuse batch requests for no series or more than `--max-series-per-request`.
+fn check_series_count(app_state: &AppState, count: usize) -> Result<(), FredApiError> {
+    let max = app_state.max_series_per_request;
+    if count == 0 || count > max {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!("series_ids must list between 1 and {max} series")),
+        });
+    }
+    Ok(())
+}
+
+/// Merge the tags of several series, counting how many of them carry each tag. The most common
+/// tags come first.
+fn aggregate_tags(tag_lists: &[FredResponseTags]) -> Vec<AggregatedTag> {
+    let mut aggregated = Vec::<AggregatedTag>::new();
+    for tag in tag_lists.iter().flat_map(|tags| &tags.tags) {
+        match aggregated.iter_mut().find(|t| t.name == tag.name) {
+            Some(existing) => existing.count += 1,
+            None => aggregated.push(AggregatedTag {
+                name: tag.name.clone(),
+                group_id: tag.group_id.clone(),
+                notes: tag.notes.clone(),
+                popularity: tag.popularity,
+                count: 1,
+            }),
+        }
+    }
+    aggregated.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.popularity.cmp(&a.popularity))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    aggregated
+}
+
+async fn delete_cache_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ClearCacheParams>,
+) -> Result<StatusCode, FredApiError> {
+    app_state.require_admin(&headers)?;
+    app_state
         .realtime_observations_db
-        .get_observations(
+        .clear_all(params.vacuum)
+        .await?;
+    app_state.precompressed_responses.clear();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Forget what is cached of one series, e.g. after FRED revised it heavily, so that the next
+/// request refetches it from FRED.
+async fn delete_cached_series_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(series_id): Path<String>,
+) -> Result<Json<SeriesDeletion>, FredApiError> {
+    app_state.require_admin(&headers)?;
+    let deleted = app_state
+        .realtime_observations_db
+        .delete_series_observations(&series_id)
+        .await?;
+    app_state
+        .precompressed_responses
+        .invalidate_series(&series_id);
+    Ok(Json(SeriesDeletion { series_id, deleted }))
+}
+
+/// Compare a series' cached observations with FRED's current ones, for monitoring the cache for
+/// drift. Changes nothing.
+async fn get_diff_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GetDiffParams>,
+) -> Result<Json<CacheDiff>, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    app_state.check_series_allowed(&params.series_id)?;
+    let live = async {
+        request_observations_from_fred(
+            &app_state.fred,
             &params.series_id,
             params.observation_start,
             params.observation_end,
+            None,
+            None,
+            FredTransformation::default(),
         )
         .await
-        .map_err(|_| FredApiError::default())?;
-    // Check if the cache hit by only checking the `observation_end` boundary.
-    // No need to check the beginning. Assume that if the series is present in the database,
-    // it has all historical observations available.
-    if !cached.is_empty()
-        && params.observation_end.is_some()
-        && params.observation_end.unwrap() <= cached.last().unwrap().date
-    {
-        return Ok(Json(cached));
-    }
-    // Cache miss--so go out to the FRED API to get the requested observations.
-    let fresh_observations = request_observations_from_fred(
-        app_state.client.clone(),
-        &app_state.fred_api_key,
+    };
+    let cached = async {
+        app_state
+            .realtime_observations_db
+            .get_observations(
+                &params.series_id,
+                params.observation_start,
+                params.observation_end,
+            )
+            .await
+            .map_err(FredApiError::from)
+    };
+    let (live, cached) = tokio::try_join!(live, cached)?;
+    Ok(Json(diff_observations(&live.observations, &cached)))
+}
+
+/// Walk two date-ordered lists of observations side by side, noting where they disagree.
+fn diff_observations(live: &[RealtimeObservation], cached: &[RealtimeObservation]) -> CacheDiff {
+    let mut diff = CacheDiff::default();
+    let (mut live, mut cached) = (live.iter().peekable(), cached.iter().peekable());
+    loop {
+        match (live.peek(), cached.peek()) {
+            (Some(l), Some(c)) if l.date == c.date => {
+                if l.value != c.value {
+                    diff.differing_dates.push(l.date);
+                }
+                live.next();
+                cached.next();
+            }
+            (Some(l), Some(c)) if l.date < c.date => {
+                diff.missing_in_cache += 1;
+                live.next();
+            }
+            (Some(_), None) => {
+                diff.missing_in_cache += 1;
+                live.next();
+            }
+            (_, Some(_)) => {
+                diff.extra_in_cache += 1;
+                cached.next();
+            }
+            (None, None) => return diff,
+        }
+    }
+}
+
+/// Fetch observations into the local cache like `/v0/observations` would, without sending them.
+async fn post_cache_warm_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<WarmCacheParams>,
+) -> Result<Json<WarmCacheResponse>, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    if !app_state.caches_series(&params.series_id) {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!("{} is never cached", params.series_id)),
+        });
+    }
+    let timing = ServerTiming::default();
+    let params = GetObservationsParams {
+        series_id: params.series_id,
+        observation_start: params.observation_start,
+        observation_end: params.observation_end,
+        ..Default::default()
+    };
+    fetch_observations(&app_state, &params, &timing).await?;
+    Ok(Json(WarmCacheResponse {
+        rows_written: timing.rows_written(),
+    }))
+}
+
+async fn get_cached_series_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<CachedSeries>>, FredApiError> {
+    let cached_series = app_state
+        .realtime_observations_db
+        .list_cached_series()
+        .await?;
+    Ok(Json(cached_series))
+}
+
+async fn get_cache_size_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<CacheSize>, FredApiError> {
+    let size = app_state.realtime_observations_db.size().await?;
+    Ok(Json(size))
+}
+
+/// How long `/healthz` waits for the database, e.g. for a connection while all are in use
+const HEALTH_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// For liveness and readiness probes: whether the local cache answers, without asking FRED.
+async fn get_health_handler(State(app_state): State<AppState>) -> (StatusCode, Json<Health>) {
+    let ping = tokio::time::timeout(
+        HEALTH_CHECK_TIMEOUT,
+        app_state.realtime_observations_db.ping(),
+    )
+    .await;
+    let error = match ping {
+        Ok(Ok(())) => {
+            return (
+                StatusCode::OK,
+                Json(Health {
+                    status: "ok".to_string(),
+                    error: None,
+                }),
+            )
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => format!("no answer from the database in {HEALTH_CHECK_TIMEOUT:?}"),
+    };
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(Health {
+            status: "unavailable".to_string(),
+            error: Some(error),
+        }),
+    )
+}
+
+/// Check that the local cache can be written, read, and deleted from, without asking FRED.
+async fn post_self_test_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<SelfTestResult>), FredApiError> {
+    app_state.require_admin(&headers)?;
+    Ok(match app_state.realtime_observations_db.self_test().await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(SelfTestResult {
+                passed: true,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(SelfTestResult {
+                passed: false,
+                error: Some(e.to_string()),
+            }),
+        ),
+    })
+}
+
+async fn get_upstream_status_handler(State(app_state): State<AppState>) -> Json<UpstreamStatus> {
+    Json(app_state.upstream_status.get(&app_state.fred).await)
+}
+
+async fn get_fetch_errors_handler(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<SeriesFetchError>>, FredApiError> {
+    let errors = app_state
+        .realtime_observations_db
+        .list_fetch_errors()
+        .await?;
+    Ok(Json(errors))
+}
+
+async fn get_observations_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    RawQuery(mut query): RawQuery,
+    Query(mut params): Query<GetObservationsParams>,
+) -> Result<Response, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    // Without `format`, `Accept: text/csv` asks for CSV, so the response varies by `Accept`.
+    let negotiated = !normalized_query(query.as_deref().unwrap_or_default())
+        .split('&')
+        .any(|pair| pair.split('=').next() == Some("format"));
+    if negotiated && prefers_csv(&headers) {
+        params.format = ObservationsFormat::Csv;
+        // keep CSV and JSON responses apart in the precompressed response cache
+        query = Some(match query {
+            Some(query) if !query.is_empty() => format!("{query}&format=csv"),
+            _ => "format=csv".to_string(),
+        });
+    }
+    let clamped_from = clamp_to_series_start(&app_state, &mut params).await?;
+    let mut response = observations_response(app_state, headers, query, params.clone()).await?;
+    if negotiated {
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("accept"));
+    }
+    if let (Some(requested), Some(series_start)) = (clamped_from, params.observation_start) {
+        let warning = format!(
+            "199 - \"observation_start {requested} is before {} begins; its observations start {series_start}\"",
+            params.series_id
+        );
+        if let Ok(warning) = HeaderValue::from_str(&warning) {
+            response.headers_mut().insert(header::WARNING, warning);
+        }
+    }
+    Ok(response)
+}
+
+/// Move an `observation_start` before the series' first observation, per its cached metadata, up
+/// to that observation, so that the dates before it aren't asked of FRED again and again.
+/// Returns the requested start if it was moved.
+async fn clamp_to_series_start(
+    app_state: &AppState,
+    params: &mut GetObservationsParams,
+) -> Result<Option<NaiveDate>, FredApiError> {
+    let Some(requested) = params.observation_start else {
+        return Ok(None);
+    };
+    if params.raw || !app_state.caches_series(&params.series_id) || requests_vintage(params) {
+        return Ok(None);
+    }
+    let fetched_since =
+        staleness_cutoff(app_state.effective_max_staleness(params.max_staleness_secs));
+    let series = app_state
+        .realtime_observations_db
+        .get_series(&params.series_id, fetched_since)
+        .await?;
+    match series {
+        Some(series) if series.observation_start > requested => {
+            params.observation_start = Some(series.observation_start);
+            Ok(Some(requested))
+        }
+        _ => Ok(None),
+    }
+}
+
+async fn observations_response(
+    app_state: AppState,
+    headers: HeaderMap,
+    query: Option<String>,
+    mut params: GetObservationsParams,
+) -> Result<Response, FredApiError> {
+    let timing = ServerTiming::default();
+    if params.raw {
+        return raw_observations(&app_state, &params, &timing).await;
+    }
+    if let Some(since) = params.if_modified_since_updated {
+        let series = timing
+            .time("series", fetch_series(&app_state, &params.series_id))
+            .await?;
+        if series.last_updated <= since {
+            return Ok(with_server_timing(
+                StatusCode::NOT_MODIFIED.into_response(),
+                &timing,
+            ));
+        }
+        // observations cached before the update may be out of date
+        let updated_secs_ago = (chrono::Utc::now() - series.last_updated)
+            .num_seconds()
+            .max(0) as u64;
+        params.max_staleness_secs = Some(
+            params
+                .max_staleness_secs
+                .map_or(updated_secs_ago, |secs| secs.min(updated_secs_ago)),
+        );
+    }
+    if params.include_meta || params.debug || params.validate || params.page_size.is_some() {
+        if params.format != ObservationsFormat::Json {
+            return Err(FredApiError {
+                status_code: StatusCode::BAD_REQUEST,
+                error_message: Some(
+                    "include_meta, debug, validate, and page_size are only supported for JSON output"
+                        .to_string(),
+                ),
+            });
+        }
+        let page = page_params(&params)?;
+        let series = async {
+            if !params.include_meta && !params.validate {
+                return Ok(None);
+            }
+            timing
+                .time("series", fetch_series(&app_state, &params.series_id))
+                .await
+                .map(Some)
+        };
+        let observations = async {
+            let fetched = fetch_observations(&app_state, &page, &timing).await?;
+            fallback_to_latest(&app_state, &page, fetched).await
+        };
+        let (series, (mut observations, source)) = tokio::try_join!(series, observations)?;
+        let next_cursor = params
+            .page_size
+            .and_then(|page_size| paginate(&mut observations, page_size));
+        // judged on the values as FRED published them, before any resampling or transform
+        let warnings = match (&series, params.validate) {
+            (Some(series), true) => Some(validate_observations(series, &observations)),
+            _ => None,
+        };
+        let observations = transformed(observations, &params)?;
+        let (observations, total) = windowed(observations, &params);
+        let summary = ObservationSummary {
+            total,
+            ..ObservationSummary::of(&observations)
+        };
+        let observations = sorted(observations, params.sort_order);
+        let started = Instant::now();
+        let response = Json(ObservationsEnvelope {
+            request: params.debug.then(|| resolved_params(&app_state, &params)),
+            upstream: params.debug.then(|| UpstreamStats {
+                pages: timing.fred_pages(),
+                duration_ms: timing.duration("fred").as_secs_f64() * 1000.0,
+            }),
+            series: series.filter(|_| params.include_meta).map(Into::into),
+            meta: params
+                .include_meta
+                .then(|| observations_meta(source, &timing)),
+            warnings,
+            next_cursor,
+            observations: FormattedObservations {
+                observations: &observations,
+                date_format: params.date_format,
+                include_realtime: params.include_realtime,
+            },
+        })
+        .into_response();
+        timing.record("serialize", started.elapsed());
+        return Ok(with_server_timing(
+            with_observation_summary(with_data_source(response, source), &summary),
+            &timing,
+        ));
+    }
+    // Reuse the compressed body of an identical earlier request while the cached observations
+    // it was built from are unchanged (writes for the series invalidate it). Only bodies of
+    // cached observations reaching `observation_end` are kept: any other cache hit rests on
+    // FRED's word that nothing more is out yet, which expires without a write to notice.
+    let precompress = app_state.caches_series(&params.series_id)
+        && app_state.precompressed_responses.is_enabled()
+        && accepts_gzip(&headers)
+        && !requests_vintage(&params)
+        && params.observation_end.is_some();
+    let query = normalized_query(query.as_deref().unwrap_or_default());
+    if precompress
+        && !timing
+            .time("cache_read", has_stale_observations(&app_state, &params))
+            .await?
+    {
+        if let Some(precompressed) = app_state
+            .precompressed_responses
+            .get(&params.series_id, &query)
+        {
+            record_access(&app_state, &params.series_id).await;
+            let summary = precompressed.summary.clone();
+            return Ok(with_server_timing(
+                with_observation_summary(
+                    with_data_source(precompressed_response(precompressed), DataSource::Cache),
+                    &summary,
+                ),
+                &timing,
+            ));
+        }
+    }
+    let fetched = fetch_observations(&app_state, &params, &timing).await?;
+    let (observations, source) = fallback_to_latest(&app_state, &params, fetched).await?;
+    let covered = matches!(
+        (observations.last(), params.observation_end),
+        (Some(last), Some(observation_end)) if app_state.covers_through(last.date, observation_end)
+    );
+    let observations = transformed(observations, &params)?;
+    let (observations, total) = windowed(observations, &params);
+    let summary = ObservationSummary {
+        total,
+        ..ObservationSummary::of(&observations)
+    };
+    let observations = sorted(observations, params.sort_order);
+    let started = Instant::now();
+    let (content_type, body) = render_observations(&observations, &params)?;
+    if precompress && source == DataSource::Cache && covered {
+        let precompressed = app_state
+            .precompressed_responses
+            .insert(
+                &params.series_id,
+                &query,
+                content_type,
+                &body,
+                summary.clone(),
+            )
+            .map_err(|_| FredApiError::default())?;
+        timing.record("serialize", started.elapsed());
+        return Ok(with_server_timing(
+            with_observation_summary(
+                with_data_source(precompressed_response(precompressed), DataSource::Cache),
+                &summary,
+            ),
+            &timing,
+        ));
+    }
+    timing.record("serialize", started.elapsed());
+    Ok(with_server_timing(
+        with_observation_summary(
+            with_data_source(
+                ([(header::CONTENT_TYPE, content_type)], body).into_response(),
+                source,
+            ),
+            &summary,
+        ),
+        &timing,
+    ))
+}
+
+/// The request's parameters narrowed to the page after its `cursor`, if it pages through the
+/// observations with `page_size`.
+fn page_params(params: &GetObservationsParams) -> Result<GetObservationsParams, FredApiError> {
+    let bad_request = |message: &str| FredApiError {
+        status_code: StatusCode::BAD_REQUEST,
+        error_message: Some(message.to_string()),
+    };
+    let Some(page_size) = params.page_size else {
+        if params.cursor.is_some() {
+            return Err(bad_request("cursor requires page_size"));
+        }
+        return Ok(params.clone());
+    };
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        return Err(bad_request(&format!(
+            "page_size must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+    // each of these would need the observations before the page, or say which to return itself
+    if params.resample.is_some()
+        || params.transform.is_some()
+        || params.rebase_date.is_some()
+        || params.rebase.is_some()
+        || params.tail.is_some()
+        || params.fallback_to_latest
+        || params.sort_order == SortOrder::Desc
+        || params.limit.is_some()
+        || params.offset.is_some()
+    {
+        return Err(bad_request(
+            "page_size can't be combined with resample, transform, rebase, tail, fallback_to_latest, sort_order=desc, limit, or offset",
+        ));
+    }
+    let Some(cursor) = &params.cursor else {
+        return Ok(params.clone());
+    };
+    let after = decode_cursor(cursor).ok_or_else(|| bad_request("invalid cursor"))?;
+    Ok(GetObservationsParams {
+        observation_start: after.succ_opt().max(params.observation_start),
+        cursor: None,
+        ..params.clone()
+    })
+}
+
+/// Cut the observations down to a page of `page_size`, returning the cursor to resume after it
+/// if any were cut.
+fn paginate(observations: &mut Vec<RealtimeObservation>, page_size: usize) -> Option<String> {
+    if observations.len() <= page_size {
+        return None;
+    }
+    observations.truncate(page_size);
+    observations.last().map(|last| encode_cursor(last.date))
+}
+
+/// Opaque cursor resuming after the observation on `date`
+fn encode_cursor(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d")
+        .to_string()
+        .bytes()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn decode_cursor(cursor: &str) -> Option<NaiveDate> {
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    NaiveDate::parse_from_str(std::str::from_utf8(&bytes).ok()?, "%Y-%m-%d").ok()
+}
+
+/// Summarize the observations returned in headers, for clients that only need a value and a
+/// range and would rather not parse the body.
+fn with_observation_summary(mut response: Response, summary: &ObservationSummary) -> Response {
+    let headers = response.headers_mut();
+    let values = [
+        (OBS_MIN_HEADER, summary.min.clone()),
+        (OBS_MAX_HEADER, summary.max.clone()),
+        (OBS_LAST_HEADER, summary.last.clone()),
+        (OBS_COUNT_HEADER, Some(summary.count.to_string())),
+        (
+            TOTAL_COUNT_HEADER,
+            summary.total.map(|total| total.to_string()),
+        ),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value.and_then(|value| HeaderValue::from_str(&value).ok()) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+    response
+}
+
+/// Say where the data came from, in `X-Data-Source` and for the access log, and tell apart a
+/// series with no observations yet from a request that merely matched none.
+fn with_data_source(mut response: Response, source: DataSource) -> Response {
+    response.extensions_mut().insert(source);
+    response.headers_mut().insert(
+        HeaderName::from_static(DATA_SOURCE_HEADER),
+        HeaderValue::from_static(source.as_str()),
+    );
+    let marker = match source {
+        DataSource::Empty => Some(SERIES_EMPTY_HEADER),
+        DataSource::LatestBeforeRange => Some(OUTSIDE_RANGE_HEADER),
+        _ => None,
+    };
+    if let Some(marker) = marker {
+        response.headers_mut().insert(
+            HeaderName::from_static(marker),
+            HeaderValue::from_static("true"),
+        );
+    }
+    response
+}
+
+/// With `fallback_to_latest`, stand in for a requested range without observations with the latest
+/// cached observation before it, so that alerting on a series' latest value doesn't see a gap
+/// just because nothing was published in the range.
+async fn fallback_to_latest(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+    fetched: (Vec<RealtimeObservation>, DataSource),
+) -> Result<(Vec<RealtimeObservation>, DataSource), FredApiError> {
+    let Some(observation_start) = params.observation_start else {
+        return Ok(fetched);
+    };
+    if !params.fallback_to_latest
+        || !fetched.0.is_empty()
+        || !app_state.caches_series(&params.series_id)
+        || requests_vintage(params)
+        || params.fred_transformation().transforms()
+    {
+        return Ok(fetched);
+    }
+    let latest = app_state
+        .realtime_observations_db
+        .get_latest_observations(&params.series_id, None, observation_start.pred_opt(), 1)
+        .await?;
+    if latest.is_empty() {
+        return Ok(fetched);
+    }
+    Ok((latest, DataSource::LatestBeforeRange))
+}
+
+/// The observations on just the given dates, read from the range of the cache spanning them.
+async fn post_observations_at_handler(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(params): Json<GetObservationsAtParams>,
+) -> Result<Response, FredApiError> {
+    let app_state = app_state.for_request(&headers)?;
+    let (Some(&start), Some(&end)) = (params.dates.iter().min(), params.dates.iter().max()) else {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some("dates must not be empty".to_string()),
+        });
+    };
+    if params.dates.len() > MAX_DATES {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!("dates must list at most {MAX_DATES} dates")),
+        });
+    }
+    let range = GetObservationsParams {
+        series_id: params.series_id,
+        observation_start: Some(start),
+        observation_end: Some(end),
+        ..Default::default()
+    };
+    let (observations, source) =
+        fetch_observations(&app_state, &range, &Default::default()).await?;
+    let observations_at = params
+        .dates
+        .into_iter()
+        .map(|date| ObservationAt {
+            date,
+            value: observations
+                .binary_search_by_key(&date, |o| o.date)
+                .ok()
+                .map(|i| observations[i].value.clone()),
+        })
+        .collect::<Vec<_>>();
+    Ok(with_data_source(
+        Json(observations_at).into_response(),
+        source,
+    ))
+}
+
+/// Report how long each phase of handling the request took.
+fn with_server_timing(mut response: Response, timing: &ServerTiming) -> Response {
+    if let Ok(value) = HeaderValue::from_str(&timing.header_value()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static("server-timing"), value);
+    }
+    response
+}
+
+/// The request's parameters with the server's defaults and limits applied, as echoed by `debug`.
+fn resolved_params(app_state: &AppState, params: &GetObservationsParams) -> GetObservationsParams {
+    GetObservationsParams {
+        max_staleness_secs: app_state.effective_max_staleness(params.max_staleness_secs),
+        ..params.clone()
+    }
+}
+
+/// Apply the `resample`, `rebase`, `transform`, and `round` the request asked for, if any, in
+/// that order.
+fn transformed(
+    observations: Vec<RealtimeObservation>,
+    params: &GetObservationsParams,
+) -> Result<Vec<RealtimeObservation>, FredApiError> {
+    let observations = match params.resample {
+        Some(period) => resample(&observations, period, params.agg),
+        None => observations,
+    };
+    let observations = match (params.rebase_date, params.rebase) {
+        (None, None) => observations,
+        (base_date, _) => rebase(observations, base_date).ok_or_else(|| FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(match base_date {
+                Some(base_date) => format!("no nonzero value on {base_date} to rebase to"),
+                None => "no nonzero value to rebase to".to_string(),
+            }),
+        })?,
+    };
+    let observations = match params.transform {
+        Some(transform) => apply_transform(&observations, transform),
+        None => observations,
+    };
+    Ok(match params.round {
+        Some(places) => round_values(observations, places),
+        None => observations,
+    })
+}
+
+/// Cut the `limit` observations after `offset`, counted in `sort_order`, out of observations
+/// ordered oldest first, keeping them oldest first. Returns how many there were before, if the
+/// request asked for a window.
+fn windowed(
+    mut observations: Vec<RealtimeObservation>,
+    params: &GetObservationsParams,
+) -> (Vec<RealtimeObservation>, Option<usize>) {
+    if params.limit.is_none() && params.offset.is_none() {
+        return (observations, None);
+    }
+    let total = observations.len();
+    let skip = params.offset.unwrap_or(0).min(total);
+    let take = params.limit.unwrap_or(total).min(total - skip);
+    let start = match params.sort_order {
+        SortOrder::Asc => skip,
+        SortOrder::Desc => total - skip - take,
+    };
+    observations.truncate(start + take);
+    observations.drain(..start);
+    (observations, Some(total))
+}
+
+/// Put observations, which are oldest first until they are returned, in the requested order.
+fn sorted(
+    mut observations: Vec<RealtimeObservation>,
+    order: SortOrder,
+) -> Vec<RealtimeObservation> {
+    if order == SortOrder::Desc {
+        observations.reverse();
+    }
+    observations
+}
+
+/// Serialize observations in the format the request asked for, returning the content type
+/// along with the body.
+fn render_observations(
+    observations: &[RealtimeObservation],
+    params: &GetObservationsParams,
+) -> Result<(&'static str, Vec<u8>), FredApiError> {
+    if params.include_realtime && params.format != ObservationsFormat::Json {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some("include_realtime is only supported for JSON output".to_string()),
+        });
+    }
+    match params.format {
+        ObservationsFormat::Json => Ok((
+            "application/json",
+            serde_json::to_vec(&FormattedObservations {
+                observations,
+                date_format: params.date_format,
+                include_realtime: params.include_realtime,
+            })
+            .map_err(|_| FredApiError::default())?,
+        )),
+        ObservationsFormat::Csv => Ok((
+            "text/csv; charset=utf-8",
+            observations_to_csv(observations, params.date_format, params.excel).into_bytes(),
+        )),
+        ObservationsFormat::Map => Ok((
+            "application/json",
+            serde_json::to_vec(&ObservationsByDate {
+                observations,
+                date_format: params.date_format,
+            })
+            .map_err(|_| FredApiError::default())?,
+        )),
+    }
+}
+
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let rejected = parts.any(|param| param.replace(' ', "") == "q=0");
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !rejected
+        })
+}
+
+/// Whether the `Accept` header lists `text/csv` before (or without) `application/json`
+fn prefers_csv(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_range| {
+            let mut parts = media_range.split(';').map(str::trim);
+            let essence = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let rejected = parts.any(|param| param.replace(' ', "") == "q=0");
+            (!rejected).then_some(essence)
+        })
+        .find(|essence| essence == "text/csv" || essence == "application/json")
+        .is_some_and(|essence| essence == "text/csv")
+}
+
+/// Query string with its parameters sorted, so equivalent requests share a cache key.
+fn normalized_query(query: &str) -> String {
+    let mut pairs: Vec<&str> = query.split('&').filter(|pair| !pair.is_empty()).collect();
+    pairs.sort_unstable();
+    pairs.join("&")
+}
+
+fn precompressed_response(precompressed: PrecompressedResponse) -> Response {
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(precompressed.content_type),
+            ),
+            (header::CONTENT_ENCODING, HeaderValue::from_static("gzip")),
+            (header::VARY, HeaderValue::from_static("accept-encoding")),
+        ],
+        precompressed.gzipped,
+    )
+        .into_response()
+}
+
+/// Where the observations in a response came from, for `include_meta`
+fn observations_meta(source: DataSource, timing: &ServerTiming) -> ObservationsMeta {
+    let fetched_ranges: Vec<DateRange> = timing
+        .fred_ranges()
+        .into_iter()
+        .map(|(start, end)| DateRange { start, end })
+        .collect();
+    let asked_fred = timing.fred_pages() > 0;
+    let source = match source {
+        DataSource::Merged => ObservationsSource::Mixed,
+        DataSource::Upstream => ObservationsSource::Fred,
+        // FRED may have just said the series has no observations
+        DataSource::Empty if asked_fred => ObservationsSource::Fred,
+        DataSource::Cache | DataSource::Empty | DataSource::LatestBeforeRange => {
+            ObservationsSource::Cache
+        }
+    };
+    ObservationsMeta {
+        cache_hit: !asked_fred,
+        fetched_ranges,
+        source,
+    }
+}
+
+/// Where the observations in a response came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DataSource {
+    /// Served entirely from the local cache
+    Cache,
+    /// Fetched entirely from FRED
+    Upstream,
+    /// Cached observations extended with ones fetched from FRED
+    Merged,
+    /// None, because FRED reported that the series has no observations yet
+    Empty,
+    /// The latest cached observation before the requested range, which had none
+    /// (`fallback_to_latest`)
+    LatestBeforeRange,
+}
+
+impl DataSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            DataSource::Cache => "cache",
+            DataSource::Upstream => "upstream",
+            DataSource::Merged => "merged",
+            DataSource::Empty => "empty",
+            DataSource::LatestBeforeRange => "latest_before_range",
+        }
+    }
+}
+
+/// Whether the request asks for data as of some other realtime period than today, which the
+/// local cache (holding only the latest vintage) cannot answer. Pinning both ends of the
+/// realtime period to today is the same as not passing them at all.
+fn requests_vintage(params: &GetObservationsParams) -> bool {
+    let today = chrono::Utc::now().date_naive();
+    match (params.realtime_start, params.realtime_end) {
+        (None, None) => false,
+        (Some(start), Some(end)) => start != today || end != today,
+        _ => true,
+    }
+}
+
+/// The realtime period the request asks for, if `--vintage-cache` may answer it: one that ended
+/// before today, so that FRED's answer for it is final.
+fn past_vintage(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+) -> Option<(NaiveDate, NaiveDate)> {
+    let (Some(realtime_start), Some(realtime_end)) = (params.realtime_start, params.realtime_end)
+    else {
+        return None;
+    };
+    let today = chrono::Utc::now().date_naive();
+    (app_state.vintage_cache
+        && app_state.caches_series(&params.series_id)
+        && !params.fred_transformation().transforms()
+        && realtime_start <= realtime_end
+        && realtime_end < today)
+        .then_some((realtime_start, realtime_end))
+}
+
+/// Serve a past vintage from the vintage cache, first fetching all of the series as of then
+/// from FRED if it isn't cached yet. Never touches the cache of current observations.
+async fn fetch_vintage(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+    realtime_start: NaiveDate,
+    realtime_end: NaiveDate,
+    timing: &ServerTiming,
+) -> Result<(Vec<RealtimeObservation>, DataSource), FredApiError> {
+    let db = &app_state.realtime_observations_db;
+    let cached = timing
+        .time(
+            "cache_read",
+            db.get_vintage_observations(
+                &params.series_id,
+                realtime_start,
+                realtime_end,
+                params.observation_start,
+                params.observation_end,
+            ),
+        )
+        .await?;
+    let (mut observations, source) = match cached {
+        Some(cached) => (cached, DataSource::Cache),
+        None => {
+            let fresh = fetch_from_fred(
+                app_state,
+                timing,
+                &params.series_id,
+                request_observations_from_fred(
+                    &app_state.fred,
+                    &params.series_id,
+                    None,
+                    None,
+                    Some(realtime_start),
+                    Some(realtime_end),
+                    FredTransformation::default(),
+                ),
+            )
+            .await?;
+            timing
+                .time(
+                    "cache_write",
+                    db.put_vintage_observations(
+                        &params.series_id,
+                        realtime_start,
+                        realtime_end,
+                        &fresh.observations,
+                    ),
+                )
+                .await?;
+            timing.add_rows_written(fresh.observations.len());
+            let since = params.observation_start.unwrap_or(NaiveDate::MIN);
+            let until = params.observation_end.unwrap_or(NaiveDate::MAX);
+            let mut observations = fresh.observations;
+            observations.retain(|o| o.date >= since && o.date <= until);
+            (observations, DataSource::Upstream)
+        }
+    };
+    if let Some(tail) = params.tail {
+        observations.drain(..observations.len().saturating_sub(tail));
+    }
+    Ok((observations, source))
+}
+
+/// Whether any cached observation in the requested range is older than the request accepts.
+async fn has_stale_observations(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+) -> Result<bool, FredApiError> {
+    let fetched_before =
+        staleness_cutoff(app_state.effective_max_staleness(params.max_staleness_secs));
+    let Some(fetched_before) = fetched_before else {
+        return Ok(false);
+    };
+    app_state
+        .realtime_observations_db
+        .has_stale_observations(
+            &params.series_id,
+            params.observation_start,
+            params.observation_end,
+            fetched_before,
+        )
+        .await
+        .map_err(FredApiError::from)
+}
+
+/// The time before which FRED's word that there are no observations is too old for the request
+/// to trust.
+fn empty_recheck_cutoff(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+) -> chrono::DateTime<chrono::Utc> {
+    staleness_cutoff(Some(
+        app_state
+            .effective_max_staleness(params.max_staleness_secs)
+            .unwrap_or(EMPTY_SERIES_RECHECK_SECS),
+    ))
+    .unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC)
+}
+
+/// Whether FRED recently enough for the request said that the series has no observations
+/// between `since` and `until`.
+async fn is_range_known_empty(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    timing: &ServerTiming,
+) -> Result<bool, FredApiError> {
+    timing
+        .time(
+            "cache_read",
+            app_state.realtime_observations_db.is_range_known_empty(
+                &params.series_id,
+                since,
+                until,
+                empty_recheck_cutoff(app_state, params),
+            ),
+        )
+        .await
+        .map_err(FredApiError::from)
+}
+
+/// The time before which anything fetched is older than `max_staleness_secs`, or `None` if
+/// nothing goes stale.
+fn staleness_cutoff(max_staleness_secs: Option<u64>) -> Option<chrono::DateTime<chrono::Utc>> {
+    max_staleness_secs
+        .and_then(|secs| chrono::Duration::from_std(std::time::Duration::from_secs(secs)).ok())
+        .and_then(|max_staleness| chrono::Utc::now().checked_sub_signed(max_staleness))
+}
+
+/// Keep the series from being evicted for a while longer, if anything is evicted. A failure to
+/// record it is no reason to fail a request the cache could answer, so it is only logged.
+async fn record_access(app_state: &AppState, series_id: &str) {
+    if !app_state.track_access {
+        return;
+    }
+    if let Err(e) = app_state
+        .realtime_observations_db
+        .record_access(series_id)
+        .await
+    {
+        tracing::warn!(series_id, error = %e, "failed to record access to series");
+    }
+}
+
+/// Write observations fetched from FRED to the local cache.
+async fn store_observations(
+    app_state: &AppState,
+    timing: &ServerTiming,
+    series_id: &str,
+    observations: &[RealtimeObservation],
+) -> Result<(), FredApiError> {
+    // An empty response says nothing about the range being empty for good (e.g. FRED may have
+    // left out the observations during an outage), so there is nothing to record.
+    if observations.is_empty() {
+        return Ok(());
+    }
+    app_state
+        .realtime_observations_db
+        .put_observations(series_id, observations)
+        .await?;
+    timing.add_rows_written(observations.len());
+    app_state
+        .precompressed_responses
+        .invalidate_series(series_id);
+    Ok(())
+}
+
+/// FRED's observations response as FRED sent it, for `raw=true`. Nothing the proxy would do to
+/// the observations applies, so asking for any of it is an error rather than silently ignored.
+async fn raw_observations(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+    timing: &ServerTiming,
+) -> Result<Response, FredApiError> {
+    app_state.check_series_allowed(&params.series_id)?;
+    let reshaped = params.format != ObservationsFormat::Json
+        || params.include_meta
+        || params.debug
+        || params.validate
+        || params.include_realtime
+        || params.fallback_to_latest
+        || params.tail.is_some()
+        || params.transform.is_some()
+        || params.resample.is_some()
+        || params.rebase_date.is_some()
+        || params.rebase.is_some()
+        || params.round.is_some()
+        || params.page_size.is_some()
+        || params.sort_order != SortOrder::Asc
+        || params.limit.is_some()
+        || params.offset.is_some()
+        || params.if_modified_since_updated.is_some();
+    if reshaped {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(
+                "raw only combines with series_id and the observation and realtime dates"
+                    .to_string(),
+            ),
+        });
+    }
+    let raw = timing
+        .time(
+            "fred",
+            request_raw_observations_from_fred(
+                &app_state.fred,
+                &params.series_id,
+                params.observation_start,
+                params.observation_end,
+                params.realtime_start,
+                params.realtime_end,
+                params.fred_transformation(),
+            ),
+        )
+        .await;
+    let raw = noting_fetch_error(app_state, &params.series_id, raw).await?;
+    timing.add_fred_pages(1);
+    Ok(with_server_timing(
+        with_data_source(Json(raw).into_response(), DataSource::Upstream),
+        timing,
+    ))
+}
+
+/// Fetch observations of `series_id` from FRED, accounting the time and pages it took to
+/// `timing`.
+async fn fetch_from_fred(
+    app_state: &AppState,
+    timing: &ServerTiming,
+    series_id: &str,
+    fetch: impl Future<Output = Result<FredObservations, FredApiError>>,
+) -> Result<FredObservations, FredApiError> {
+    let fresh = noting_fetch_error(app_state, series_id, timing.time("fred", fetch).await).await?;
+    timing.add_fred_pages(fresh.pages);
+    if let (Some(first), Some(last)) = (fresh.observations.first(), fresh.observations.last()) {
+        timing.add_fred_range(first.date, last.date);
+    }
+    Ok(fresh)
+}
+
+/// With `--record-fetch-errors`, remember why fetching `series_id` from FRED failed, if it did,
+/// for `/v0/cache/errors`. The result is passed through either way.
+async fn noting_fetch_error<T>(
+    app_state: &AppState,
+    series_id: &str,
+    result: Result<T, FredApiError>,
+) -> Result<T, FredApiError> {
+    if let Err(e) = &result {
+        // a staging mirror's errors say nothing about FRED
+        if app_state.record_fetch_errors && app_state.use_cache {
+            // failing to note the error mustn't hide it
+            let _ = app_state
+                .realtime_observations_db
+                .put_fetch_error(
+                    series_id,
+                    e.status_code.as_u16(),
+                    e.error_message.as_deref(),
+                )
+                .await;
+        }
+    }
+    result
+}
+
+/// Write a fetch of every observation between `since` and `until` to the local cache, along with
+/// how many dates it held, which later reveals whether any of them went missing. FRED's own
+/// `count` won't do, because it counts every row of a date with several.
+async fn store_full_fetch(
+    app_state: &AppState,
+    timing: &ServerTiming,
+    series_id: &str,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    fresh: &FredObservations,
+) -> Result<(), FredApiError> {
+    store_observations(app_state, timing, series_id, &fresh.observations).await?;
+    if fresh.observations.is_empty() {
+        return Ok(());
+    }
+    app_state
+        .realtime_observations_db
+        .put_observation_count(series_id, since, until, fresh.observations.len())
+        .await
+        .map_err(FredApiError::from)
+}
+
+/// Whether the series has more observations than `--min-observations-to-cache`, and so is worth
+/// caching. `count` is how many a fetch found, which settles it when it exceeds the threshold or
+/// the fetch covered the `whole_series`; otherwise FRED is asked for the series' count. A fetch
+/// that found nothing has nothing to cache, so FRED isn't asked then.
+async fn is_worth_caching(
+    app_state: &AppState,
+    timing: &ServerTiming,
+    series_id: &str,
+    count: usize,
+    whole_series: bool,
+) -> Result<bool, FredApiError> {
+    let min_observations = app_state.min_observations_to_cache;
+    if min_observations == 0 {
+        return Ok(true);
+    }
+    if count > min_observations || whole_series || count == 0 {
+        return Ok(count > min_observations);
+    }
+    let latest = fetch_from_fred(
+        app_state,
+        timing,
+        series_id,
+        request_latest_observations_from_fred(
+            &app_state.fred,
+            series_id,
+            None,
+            None,
+            None,
+            None,
+            FredTransformation::default(),
+            1,
+        ),
+    )
+    .await?;
+    Ok(latest.count > min_observations)
+}
+
+/// Fetch from FRED just the requested observations before and after those cached, and then
+/// those in holes among the cached ones, and merge them into the cache. `None` if the cache
+/// still lacks observations after that, e.g. because the series' frequency doesn't tell where
+/// its holes are, or nothing in the range is cached, so the range needs refetching.
+async fn fill_gaps(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+    timing: &ServerTiming,
+) -> Result<Option<Vec<RealtimeObservation>>, FredApiError> {
+    let db = &app_state.realtime_observations_db;
+    let cached = timing
+        .time(
+            "cache_read",
+            db.get_observations(
+                &params.series_id,
+                params.observation_start,
+                params.observation_end,
+            ),
+        )
+        .await?;
+    let (Some(first), Some(last)) = (cached.first(), cached.last()) else {
+        return Ok(None);
+    };
+    // a series too small to cache is refetched whole instead
+    if !is_worth_caching(app_state, timing, &params.series_id, cached.len(), false).await? {
+        return Ok(None);
+    }
+    let day = chrono::Duration::days(1);
+    let mut before = Vec::new();
+    if params
+        .observation_start
+        .is_none_or(|start| start < first.date)
+    {
+        before = fetch_from_fred(
+            app_state,
+            timing,
+            &params.series_id,
+            request_observations_from_fred(
+                &app_state.fred,
+                &params.series_id,
+                params.observation_start,
+                Some(first.date - day),
+                None,
+                None,
+                FredTransformation::default(),
+            ),
+        )
+        .await?
+        .observations;
+    }
+    let mut after = Vec::new();
+    if params.observation_end.is_none_or(|end| end > last.date) {
+        after = fetch_from_fred(
+            app_state,
+            timing,
+            &params.series_id,
+            request_observations_from_fred(
+                &app_state.fred,
+                &params.series_id,
+                Some(last.date + day),
+                params.observation_end,
+                None,
+                None,
+                FredTransformation::default(),
+            ),
+        )
+        .await?
+        .observations;
+    }
+    for gap in [&before, &after] {
+        timing
+            .time(
+                "cache_write",
+                store_observations(app_state, timing, &params.series_id, gap),
+            )
+            .await?;
+    }
+    let is_missing_observations = || async {
+        timing
+            .time("cache_read", db.is_missing_observations(&params.series_id))
+            .await
+            .map_err(FredApiError::from)
+    };
+    let mut merged = merge_observations(
+        cached,
+        before.into_iter().chain(after),
+        db.duplicate_policy(),
+    );
+    if is_missing_observations().await? {
+        // without the series' frequency, refetch the range rather than fail
+        let frequency = timing
+            .time("series", fetch_series(app_state, &params.series_id))
+            .await
+            .ok()
+            .and_then(|series| series.frequency_enum());
+        let Some(holes) =
+            holes(&merged, frequency).filter(|holes| !holes.is_empty() && holes.len() <= MAX_HOLES)
+        else {
+            return Ok(None);
+        };
+        for (start, end) in holes {
+            let hole = fetch_from_fred(
+                app_state,
+                timing,
+                &params.series_id,
+                request_observations_from_fred(
+                    &app_state.fred,
+                    &params.series_id,
+                    Some(start),
+                    Some(end),
+                    None,
+                    None,
+                    FredTransformation::default(),
+                ),
+            )
+            .await?
+            .observations;
+            timing
+                .time(
+                    "cache_write",
+                    store_observations(app_state, timing, &params.series_id, &hole),
+                )
+                .await?;
+            merged = merge_observations(merged, hole, db.duplicate_policy());
+        }
+        if is_missing_observations().await? {
+            return Ok(None);
+        }
+    }
+    Ok(Some(merged))
+}
+
+/// Merge observations fetched from FRED into cached ones, oldest first and one per date. The
+/// segments are fetched around the cached dates, but should FRED return one on an edge anyway,
+/// `duplicate_policy` picks between the two values as the cache does when writing them, so that
+/// the response agrees with later reads from the cache.
+fn merge_observations(
+    cached: Vec<RealtimeObservation>,
+    fetched: impl IntoIterator<Item = RealtimeObservation>,
+    duplicate_policy: DuplicatePolicy,
+) -> Vec<RealtimeObservation> {
+    let mut by_date: BTreeMap<NaiveDate, RealtimeObservation> = cached
+        .into_iter()
+        .map(|observation| (observation.date, observation))
+        .collect();
+    for observation in fetched {
+        match duplicate_policy {
+            DuplicatePolicy::LatestRealtime | DuplicatePolicy::LastWritten => {
+                by_date.insert(observation.date, observation);
+            }
+            DuplicatePolicy::FirstSeen => {
+                by_date.entry(observation.date).or_insert(observation);
+            }
+        }
+    }
+    by_date.into_values().collect()
+}
+
+/// The date ranges between consecutive observations further apart than the series' frequency
+/// allows, or `None` if the frequency doesn't tell, as for daily series, which skip holidays.
+fn holes(
+    observations: &[RealtimeObservation],
+    frequency: Option<Frequency>,
+) -> Option<Vec<(NaiveDate, NaiveDate)>> {
+    let next = |date: NaiveDate| match frequency? {
+        Frequency::Daily => None,
+        Frequency::Weekly => date.checked_add_days(chrono::Days::new(7)),
+        Frequency::Biweekly => date.checked_add_days(chrono::Days::new(14)),
+        Frequency::Monthly => date.checked_add_months(chrono::Months::new(1)),
+        Frequency::Quarterly => date.checked_add_months(chrono::Months::new(3)),
+        Frequency::Semiannual => date.checked_add_months(chrono::Months::new(6)),
+        Frequency::Annual => date.checked_add_months(chrono::Months::new(12)),
+    };
+    let mut holes = Vec::new();
+    for pair in observations.windows(2) {
+        if pair[1].date > next(pair[0].date)? {
+            holes.push((pair[0].date.succ_opt()?, pair[1].date.pred_opt()?));
+        }
+    }
+    Some(holes)
+}
+
+async fn fetch_observations(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+    timing: &ServerTiming,
+) -> Result<(Vec<RealtimeObservation>, DataSource), FredApiError> {
+    app_state.check_series_allowed(&params.series_id)?;
+    if let (Some(start), Some(end)) = (params.observation_start, params.observation_end) {
+        if start > end {
+            return Err(FredApiError {
+                status_code: StatusCode::BAD_REQUEST,
+                error_message: Some(
+                    "observation_start must not be after observation_end".to_string(),
+                ),
+            });
+        }
+    }
+    if matches!(params.tail, Some(tail) if tail == 0 || tail > MAX_TAIL) {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!("tail must be between 1 and {MAX_TAIL}")),
+        });
+    }
+    if params.rebase_date.is_some() && params.rebase.is_some() {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some("rebase_date and rebase are mutually exclusive".to_string()),
+        });
+    }
+    if matches!(params.round, Some(places) if places > MAX_ROUND) {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!("round must be at most {MAX_ROUND}")),
+        });
+    }
+    if matches!(params.limit, Some(limit) if limit == 0 || limit > MAX_LIMIT) {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some(format!("limit must be between 1 and {MAX_LIMIT}")),
+        });
+    }
+    if params.aggregation_method.is_some() && params.frequency.is_none() {
+        return Err(FredApiError {
+            status_code: StatusCode::BAD_REQUEST,
+            error_message: Some("aggregation_method needs a frequency".to_string()),
+        });
+    }
+    if let Some(frequency) = params.frequency {
+        // FRED only aggregates to lower frequencies
+        let series = timing
+            .time("series", fetch_series(app_state, &params.series_id))
+            .await?;
+        if let Some(series_frequency) = series.frequency_enum() {
+            if frequency.frequency() < series_frequency {
+                return Err(FredApiError {
+                    status_code: StatusCode::BAD_REQUEST,
+                    error_message: Some(format!(
+                        "frequency must not be higher than the series' frequency, {}",
+                        series.frequency
+                    )),
+                });
+            }
+        }
+    }
+    if let Some((realtime_start, realtime_end)) = past_vintage(app_state, params) {
+        return fetch_vintage(app_state, params, realtime_start, realtime_end, timing).await;
+    }
+    // if user requested realtime/"ALFRED" data, or values transformed or aggregated by FRED,
+    // then do not use local cache
+    if !app_state.caches_series(&params.series_id)
+        || requests_vintage(params)
+        || params.fred_transformation().transforms()
+    {
+        tracing::debug!(series_id = params.series_id, "cache bypassed");
+        metrics::increment_counter!("fred_proxy_cache_bypasses_total");
+        // bypass cache
+        // because not willing to cache different versions of the same data over and over
+        let fresh = match params.tail {
+            Some(tail) => {
+                fetch_from_fred(
+                    app_state,
+                    timing,
+                    &params.series_id,
+                    request_latest_observations_from_fred(
+                        &app_state.fred,
+                        &params.series_id,
+                        params.observation_start,
+                        params.observation_end,
+                        params.realtime_start,
+                        params.realtime_end,
+                        params.fred_transformation(),
+                        tail,
+                    ),
+                )
+                .await?
+            }
+            None => {
+                fetch_from_fred(
+                    app_state,
+                    timing,
+                    &params.series_id,
+                    request_observations_from_fred(
+                        &app_state.fred,
+                        &params.series_id,
+                        params.observation_start,
+                        params.observation_end,
+                        params.realtime_start,
+                        params.realtime_end,
+                        params.fred_transformation(),
+                    ),
+                )
+                .await?
+            }
+        };
+        return Ok((fresh.observations, DataSource::Upstream));
+    }
+    record_access(app_state, &params.series_id).await;
+    if let Some(tail) = params.tail {
+        return fetch_tail(app_state, params, tail, timing).await;
+    }
+    // A range FRED recently said has no observations is not worth asking about again, e.g. one
+    // before the series begins.
+    if is_range_known_empty(
+        app_state,
+        params,
+        params.observation_start,
+        params.observation_end,
+        timing,
+    )
+    .await?
+    {
+        tracing::debug!(
+            series_id = params.series_id,
+            "cache hit: FRED recently reported no observations in the range"
+        );
+        metrics::increment_counter!("fred_proxy_cache_hits_total");
+        return Ok((Vec::new(), DataSource::Empty));
+    }
+    // Treat the whole requested range as a cache miss if any of it is older than the
+    // caller is willing to accept, or if the cache lacks observations FRED said it has.
+    let stale = timing
+        .time("cache_read", has_stale_observations(app_state, params))
+        .await?;
+    let incomplete = !stale
+        && timing
+            .time(
+                "cache_read",
+                app_state
+                    .realtime_observations_db
+                    .is_missing_observations(&params.series_id),
+            )
+            .await?;
+    if incomplete {
+        tracing::debug!(
+            series_id = params.series_id,
+            "cache incomplete: fetching around the cached observations"
+        );
+        metrics::increment_counter!("fred_proxy_cache_misses_total", "reason" => "incomplete");
+        if let Some(observations) = fill_gaps(app_state, params, timing).await? {
+            return Ok((observations, DataSource::Merged));
+        }
+    }
+    if stale || incomplete {
+        tracing::debug!(
+            series_id = params.series_id,
+            stale,
+            incomplete,
+            "cache miss: refetching the range"
+        );
+        if stale {
+            metrics::increment_counter!("fred_proxy_cache_misses_total", "reason" => "stale");
+        }
+        let fresh = fetch_from_fred(
+            app_state,
+            timing,
+            &params.series_id,
+            request_observations_from_fred(
+                &app_state.fred,
+                &params.series_id,
+                params.observation_start,
+                params.observation_end,
+                None,
+                None,
+                FredTransformation::default(),
+            ),
+        )
+        .await?;
+        let whole_series = params.observation_start.is_none() && params.observation_end.is_none();
+        if is_worth_caching(
+            app_state,
+            timing,
+            &params.series_id,
+            fresh.count,
+            whole_series,
+        )
+        .await?
+        {
+            timing
+                .time(
+                    "cache_write",
+                    store_full_fetch(
+                        app_state,
+                        timing,
+                        &params.series_id,
+                        params.observation_start,
+                        params.observation_end,
+                        &fresh,
+                    ),
+                )
+                .await?;
+        }
+        if fresh.count == 0 {
+            timing
+                .time(
+                    "cache_write",
+                    app_state.realtime_observations_db.record_empty_range(
+                        &params.series_id,
+                        params.observation_start,
+                        params.observation_end,
+                        empty_recheck_cutoff(app_state, params),
+                    ),
+                )
+                .await?;
+        }
+        return Ok((fresh.observations, DataSource::Upstream));
+    }
+    let cached = timing
+        .time(
+            "cache_read",
+            app_state.realtime_observations_db.get_observations(
+                &params.series_id,
+                params.observation_start,
+                params.observation_end,
+            ),
+        )
+        .await?;
+    // Check if the cache hit by only checking the `observation_end` boundary.
+    // No need to check the beginning. Assume that if the series is present in the database,
+    // it has all historical observations available.
+    if let (Some(last), Some(observation_end)) = (cached.last(), params.observation_end) {
+        if app_state.covers_through(last.date, observation_end) {
+            tracing::debug!(series_id = params.series_id, "cache hit");
+            metrics::increment_counter!("fred_proxy_cache_hits_total");
+            return Ok((cached, DataSource::Cache));
+        }
+    }
+    // only request after the time period we already have stored
+    let since = cached
+        .last()
+        .map(|item| item.date + chrono::Duration::days(1));
+    if is_range_known_empty(app_state, params, since, params.observation_end, timing).await? {
+        tracing::debug!(
+            series_id = params.series_id,
+            "cache hit: FRED recently reported no observations after the cache"
+        );
+        metrics::increment_counter!("fred_proxy_cache_hits_total");
+        let source = if cached.is_empty() {
+            DataSource::Empty
+        } else {
+            DataSource::Cache
+        };
+        return Ok((cached, source));
+    }
+    // Cache miss--so go out to the FRED API to get the requested observations.
+    tracing::debug!(
+        series_id = params.series_id,
+        cached_through = ?cached.last().map(|observation| observation.date),
+        "cache miss: fetching what is after the cache"
+    );
+    metrics::increment_counter!("fred_proxy_cache_misses_total", "reason" => "after_cache");
+    let fresh = fetch_from_fred(
+        app_state,
+        timing,
+        &params.series_id,
+        request_observations_from_fred(
+            &app_state.fred,
+            &params.series_id,
+            since,
+            params.observation_end,
+            None,
+            None,
+            FredTransformation::default(),
+        ),
+    )
+    .await?;
+    if fresh.count == 0 {
+        timing
+            .time(
+                "cache_write",
+                app_state.realtime_observations_db.record_empty_range(
+                    &params.series_id,
+                    since,
+                    params.observation_end,
+                    empty_recheck_cutoff(app_state, params),
+                ),
+            )
+            .await?;
+    }
+    // Update database with externally-sourced observations, unless the series is too small to be
+    // worth caching.
+    if fresh.count == 0 {
+        // the range recorded empty above is all of the series through `observation_end`
+        if cached.is_empty() {
+            return Ok((Vec::new(), DataSource::Empty));
+        }
+    } else if is_worth_caching(
+        app_state,
+        timing,
+        &params.series_id,
+        cached.len() + fresh.count,
+        cached.is_empty() && params.observation_end.is_none(),
+    )
+    .await?
+    {
+        if cached.is_empty() {
+            timing
+                .time(
+                    "cache_write",
+                    store_full_fetch(
+                        app_state,
+                        timing,
+                        &params.series_id,
+                        None,
+                        params.observation_end,
+                        &fresh,
+                    ),
+                )
+                .await?;
+        } else {
+            timing
+                .time(
+                    "cache_write",
+                    store_observations(app_state, timing, &params.series_id, &fresh.observations),
+                )
+                .await?;
+        }
+    }
+    let fresh_observations = fresh.observations;
+    let source = if cached.is_empty() {
+        DataSource::Upstream
+    } else {
+        DataSource::Merged
+    };
+    let mut observations = merge_observations(
+        cached,
+        fresh_observations,
+        app_state.realtime_observations_db.duplicate_policy(),
+    );
+    // A cold cache fetches the series' whole history, which may start before the requested range.
+    let since = params.observation_start.unwrap_or(NaiveDate::MIN);
+    observations.retain(|o| o.date >= since);
+    Ok((observations, source))
+}
+
+/// Serve `tail=N` from the cache when it holds the series through `observation_end` (or FRED
+/// recently said it has nothing more, such as when there is no `observation_end`), and
+/// otherwise ask FRED for just those rows. Tail requests skip gap-filling and never write to the
+/// cache, because the rows they fetch needn't join up with the cached history.
+async fn fetch_tail(
+    app_state: &AppState,
+    params: &GetObservationsParams,
+    tail: usize,
+    timing: &ServerTiming,
+) -> Result<(Vec<RealtimeObservation>, DataSource), FredApiError> {
+    let db = &app_state.realtime_observations_db;
+    let stale = timing
+        .time("cache_read", has_stale_observations(app_state, params))
+        .await?;
+    let incomplete = !stale
+        && timing
+            .time("cache_read", db.is_missing_observations(&params.series_id))
+            .await?;
+    let last_cached = timing
+        .time(
+            "cache_read",
+            db.get_latest_observations(&params.series_id, None, None, 1),
+        )
+        .await?;
+    if let (false, false, Some(last)) = (stale, incomplete, last_cached.last()) {
+        // The cache has the latest observations if it reaches `observation_end`, or FRED
+        // recently said there are none after it, as `fetch_observations` decides.
+        let current = match params.observation_end {
+            Some(observation_end) if app_state.covers_through(last.date, observation_end) => true,
+            _ => {
+                is_range_known_empty(
+                    app_state,
+                    params,
+                    last.date.succ_opt(),
+                    params.observation_end,
+                    timing,
+                )
+                .await?
+            }
+        };
+        if current {
+            let cached = timing
+                .time(
+                    "cache_read",
+                    db.get_latest_observations(
+                        &params.series_id,
+                        params.observation_start,
+                        params.observation_end,
+                        tail,
+                    ),
+                )
+                .await?;
+            return Ok((cached, DataSource::Cache));
+        }
+    }
+    let fresh = fetch_from_fred(
+        app_state,
+        timing,
         &params.series_id,
-        // only request after the time period we already have stored
-        cached
-            .last()
-            .map(|item| item.date + chrono::Duration::days(1)),
-        params.observation_end,
-        None,
-        None,
+        request_latest_observations_from_fred(
+            &app_state.fred,
+            &params.series_id,
+            params.observation_start,
+            params.observation_end,
+            None,
+            None,
+            FredTransformation::default(),
+            tail,
+        ),
     )
     .await?;
-    // Update database with externally-sourced observations.
-    app_state
-        .realtime_observations_db
-        .put_observations(&params.series_id, &fresh_observations)
+    Ok((fresh.observations, DataSource::Upstream))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stlouisfed_fred_web_proxy::entities::{AggregationFrequency, FredTag, Units};
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    /// App state whose FRED calls go to `fred` and whose cache lives in `db_dir`.
+    async fn test_app_state(fred: &MockServer, db_dir: &tempfile::TempDir) -> AppState {
+        let realtime_observations_db =
+            RealtimeObservationsDatabase::new(&db_dir.path().join("cache.db"))
+                .await
+                .unwrap();
+        realtime_observations_db.create_tables().await.unwrap();
+        AppState {
+            fred: FredClient::new(reqwest::Client::new(), "key")
+                .with_base_url(reqwest::Url::parse(&fred.uri()).unwrap())
+                .unwrap(),
+            realtime_observations_db,
+            cache_ttl_secs: None,
+            max_staleness_secs: None,
+            precompressed_responses: PrecompressedResponses::new(0),
+            min_observations_to_cache: 0,
+            use_cache: true,
+            no_cache_series: Default::default(),
+            series_allowlist: None,
+            admin_token: None,
+            base_url_override_hosts: Default::default(),
+            max_series_per_request: 25,
+            series_fetch_policy: SeriesFetchPolicy::Always,
+            vintage_cache: false,
+            search_cache_secs: None,
+            right_edge_grace_days: 0,
+            record_fetch_errors: false,
+            upstream_status: upstream_status::UpstreamStatusCache::new(
+                std::time::Duration::from_secs(60),
+            ),
+            track_access: false,
+        }
+    }
+
+    /// FRED `series/observations` response body with the given dates and values
+    fn fred_observations(observations: &[(&str, &str)]) -> serde_json::Value {
+        serde_json::json!({
+            "realtime_start": "2023-09-19",
+            "realtime_end": "2023-09-19",
+            "count": observations.len(),
+            "offset": 0,
+            "limit": 10000,
+            "observations": observations
+                .iter()
+                .map(|(date, value)| serde_json::json!({
+                    "realtime_start": "2023-09-19",
+                    "realtime_end": "2023-09-19",
+                    "date": date,
+                    "value": value,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn observation(date: &str, value: &str) -> RealtimeObservation {
+        RealtimeObservation {
+            date: date.parse().unwrap(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn single_day(date: &str) -> GetObservationsParams {
+        GetObservationsParams {
+            series_id: "SP500".to_string(),
+            observation_start: Some(date.parse().unwrap()),
+            observation_end: Some(date.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn dates(observations: &[RealtimeObservation]) -> Vec<String> {
+        observations.iter().map(|o| o.date.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_single_day_window_cold_miss() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_end", "2023-01-04"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-03", "3"),
+                    ("2023-01-04", "4"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let (observations, _) =
+            fetch_observations(&app_state, &single_day("2023-01-04"), &Default::default())
+                .await
+                .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-04"]);
+    }
+
+    #[tokio::test]
+    async fn test_single_day_window_exact_cache_hit() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-03", "3"),
+                    observation("2023-01-04", "4"),
+                ],
+            )
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let (observations, source) =
+            fetch_observations(&app_state, &single_day("2023-01-03"), &Default::default())
+                .await
+                .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-03"]);
+        assert_eq!(source, DataSource::Cache);
+    }
+
+    #[tokio::test]
+    async fn test_single_day_window_cache_miss() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations("SP500", &[observation("2023-01-03", "3")])
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_end", "2023-01-05"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-03", "3"),
+                    ("2023-01-04", "4"),
+                    ("2023-01-05", "5"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let (observations, _) =
+            fetch_observations(&app_state, &single_day("2023-01-05"), &Default::default())
+                .await
+                .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-05"]);
+        // and the window is now served from the cache
+        let (observations, source) =
+            fetch_observations(&app_state, &single_day("2023-01-05"), &Default::default())
+                .await
+                .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-05"]);
+        assert_eq!(source, DataSource::Cache);
+    }
+
+    #[tokio::test]
+    async fn test_realtime_pinned_to_today_uses_cache() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations("SP500", &[observation("2023-01-03", "3")])
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let today = chrono::Utc::now().date_naive();
+        let params = GetObservationsParams {
+            realtime_start: Some(today),
+            realtime_end: Some(today),
+            ..single_day("2023-01-03")
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-03"]);
+        assert_eq!(source, DataSource::Cache);
+    }
+
+    #[test]
+    fn test_requests_vintage() {
+        let today = chrono::Utc::now().date_naive();
+        let yesterday = today.pred_opt().unwrap();
+        let params = |realtime_start, realtime_end| GetObservationsParams {
+            realtime_start,
+            realtime_end,
+            ..Default::default()
+        };
+        assert!(!requests_vintage(&params(None, None)));
+        assert!(!requests_vintage(&params(Some(today), Some(today))));
+        assert!(requests_vintage(&params(Some(yesterday), Some(yesterday))));
+        assert!(requests_vintage(&params(Some(yesterday), Some(today))));
+        assert!(requests_vintage(&params(Some(today), None)));
+    }
+
+    #[tokio::test]
+    async fn test_tail_served_from_cache() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-03", "3"),
+                    observation("2023-01-04", "4"),
+                    observation("2023-01-05", "5"),
+                ],
+            )
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            series_id: "SP500".to_string(),
+            observation_end: Some("2023-01-04".parse().unwrap()),
+            tail: Some(2),
+            ..Default::default()
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-03", "2023-01-04"]);
+        assert_eq!(source, DataSource::Cache);
+    }
+
+    #[tokio::test]
+    async fn test_tail_without_observation_end_served_from_cache() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2023-01-03", "3"),
+                observation("2023-01-04", "4"),
+                observation("2023-01-05", "5"),
+            ],
+        )
+        .await
+        .unwrap();
+        // as recorded by an earlier request finding nothing after the cache
+        db.record_empty_range(
+            "SP500",
+            Some("2023-01-06".parse().unwrap()),
+            None,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            series_id: "SP500".to_string(),
+            tail: Some(2),
+            ..Default::default()
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-04", "2023-01-05"]);
+        assert_eq!(source, DataSource::Cache);
+    }
+
+    #[tokio::test]
+    async fn test_tail_miss_fetches_only_latest_from_fred() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("sort_order", "desc"))
+            .and(query_param("limit", "2"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-05", "5"),
+                    ("2023-01-04", "4"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            series_id: "SP500".to_string(),
+            tail: Some(2),
+            ..Default::default()
+        };
+        let (observations, _) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-04", "2023-01-05"]);
+        // the partial history must not be mistaken for a cached series
+        assert!(app_state
+            .realtime_observations_db
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_aggregate_tags_counts_series_per_tag() {
+        let tags = |names: &[(&str, i64)]| FredResponseTags {
+            tags: names
+                .iter()
+                .map(|(name, popularity)| FredTag {
+                    name: name.to_string(),
+                    group_id: "gen".to_string(),
+                    notes: None,
+                    created: Default::default(),
+                    popularity: *popularity,
+                    series_count: 1,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let aggregated = aggregate_tags(&[
+            tags(&[("usa", 100), ("gdp", 80)]),
+            tags(&[("usa", 100), ("employment", 90)]),
+        ]);
+        let names_and_counts: Vec<(&str, usize)> = aggregated
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.count))
+            .collect();
+        assert_eq!(
+            names_and_counts,
+            vec![("usa", 2), ("employment", 1), ("gdp", 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_series_per_request() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            max_series_per_request: 2,
+            ..test_app_state(&fred, &db_dir).await
+        };
+        Mock::given(method("GET"))
+            .and(path("/fred/series/tags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "count": 0, "offset": 0, "limit": 1000, "tags": [],
+            })))
+            .expect(2)
+            .mount(&fred)
+            .await;
+        let aggregate = |series_ids: &[&str]| {
+            post_series_tags_aggregate_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                Query(Default::default()),
+                Json(AggregateSeriesTagsParams {
+                    series_ids: series_ids.iter().map(|id| id.to_string()).collect(),
+                }),
+            )
+        };
+        assert!(aggregate(&["SP500", "GDP"]).await.is_ok());
+        let e = aggregate(&["SP500", "GDP", "UNRATE"]).await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_refetches_when_fewer_observations_cached_than_fred_reported() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        // a backfill that lost 2023-01-04 along the way
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2023-01-03", "3"),
+                observation("2023-01-05", "5"),
+            ],
+        )
+        .await
+        .unwrap();
+        db.put_observation_count("SP500", None, Some("2023-01-05".parse().unwrap()), 3)
+            .await
+            .unwrap();
+        assert!(db.is_missing_observations("SP500").await.unwrap());
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-03", "3"),
+                    ("2023-01-04", "4"),
+                    ("2023-01-05", "5"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            ..single_day("2023-01-05")
+        };
+        let (observations, _) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            dates(&observations),
+            vec!["2023-01-03", "2023-01-04", "2023-01-05"]
+        );
+        // the refetch filled the hole, so the next request is a cache hit again
+        assert!(!db.is_missing_observations("SP500").await.unwrap());
+        let (_, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(source, DataSource::Cache);
+    }
+
+    #[tokio::test]
+    async fn test_no_cache_series_bypass_cache() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            no_cache_series: Arc::new(HashSet::from(["SP500".to_string()])),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        app_state
+            .realtime_observations_db
+            .put_observations("SP500", &[observation("2023-01-03", "cached")])
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(fred_observations(&[("2023-01-03", "live")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let (observations, source) =
+            fetch_observations(&app_state, &single_day("2023-01-03"), &Default::default())
+                .await
+                .unwrap();
+        assert_eq!(observations[0].value, "live");
+        assert_eq!(source, DataSource::Upstream);
+        let cached = app_state
+            .realtime_observations_db
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap();
+        assert_eq!(cached[0].value, "cached");
+    }
+
+    #[tokio::test]
+    async fn test_units_bypass_cache() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        db.put_observations("SP500", &[observation("2023-01-03", "3")])
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("units", "pch"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(fred_observations(&[("2023-01-03", "0.5")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let uri = "/v0/observations?series_id=SP500&observation_start=2023-01-03&units=pch"
+            .parse()
+            .unwrap();
+        let Query(params) = Query::<GetObservationsParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.units, Units::Pch);
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(observations[0].value, "0.5");
+        assert_eq!(source, DataSource::Upstream);
+        // the transformed value isn't cached over the raw one
+        let cached = db.get_observations("SP500", None, None).await.unwrap();
+        assert_eq!(cached[0].value, "3");
+        let uri = "/v0/observations?series_id=SP500&units=pct"
+            .parse()
+            .unwrap();
+        assert!(Query::<GetObservationsParams>::try_from_uri(&uri).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_frequency_aggregation_bypasses_cache() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        db.put_observations("UNRATE", &[observation("2023-01-01", "3.4")])
+            .await
+            .unwrap();
+        let mut series = fred_seriess(&["UNRATE"]);
+        series["seriess"][0]["frequency"] = "Monthly".into();
+        series["seriess"][0]["frequency_short"] = "M".into();
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(series))
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("frequency", "q"))
+            .and(query_param("aggregation_method", "eop"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(fred_observations(&[("2023-01-01", "3.5")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = |query: &str| {
+            let uri = format!("/v0/observations?series_id=UNRATE&{query}")
+                .parse()
+                .unwrap();
+            Query::<GetObservationsParams>::try_from_uri(&uri).map(|Query(params)| params)
+        };
+        let quarterly = params("frequency=q&aggregation_method=eop").unwrap();
+        assert_eq!(quarterly.frequency, Some(AggregationFrequency::Quarterly));
+        let (observations, source) =
+            fetch_observations(&app_state, &quarterly, &Default::default())
+                .await
+                .unwrap();
+        assert_eq!(observations[0].value, "3.5");
+        assert_eq!(source, DataSource::Upstream);
+        // the quarterly value isn't cached over the monthly one
+        let cached = db.get_observations("UNRATE", None, None).await.unwrap();
+        assert_eq!(cached[0].value, "3.4");
+
+        // aggregating a monthly series to daily, or without a frequency, is rejected
+        for query in ["frequency=d", "aggregation_method=sum"] {
+            let e = fetch_observations(&app_state, &params(query).unwrap(), &Default::default())
+                .await
+                .unwrap_err();
+            assert_eq!(e.status_code, StatusCode::BAD_REQUEST, "{query}");
+        }
+        assert!(params("frequency=x").is_err());
+        assert!(params("frequency=q&aggregation_method=max").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_cache_requires_admin() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            admin_token: Some("secret".to_string()),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        let db = &app_state.realtime_observations_db;
+        db.put_observations("SP500", &[observation("2023-01-03", "3")])
+            .await
+            .unwrap();
+        let delete = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            );
+            delete_cache_handler(
+                State(app_state.clone()),
+                headers,
+                Query(ClearCacheParams { vacuum: true }),
+            )
+        };
+        let e = delete("wrong").await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::UNAUTHORIZED);
+        assert_eq!(db.list_cached_series().await.unwrap().len(), 1);
+        assert_eq!(delete("secret").await.unwrap(), StatusCode::NO_CONTENT);
+        assert!(db.list_cached_series().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_base_url_override_is_limited_to_allowed_hosts() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            admin_token: Some("secret".to_string()),
+            base_url_override_hosts: Arc::new(HashSet::from(["127.0.0.1".to_string()])),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        let headers = |base_url: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_static("Bearer secret"),
+            );
+            headers.insert(FRED_BASE_URL_HEADER, HeaderValue::from_static(base_url));
+            headers
+        };
+        let overridden = app_state
+            .for_request(&headers("http://127.0.0.1:1/fred/"))
+            .unwrap();
+        assert!(!overridden.use_cache);
+        let Err(e) = app_state.for_request(&headers("https://attacker.example.com/fred/")) else {
+            panic!("overriding the base URL with another host succeeded");
+        };
+        assert_eq!(e.status_code, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_self_test_requires_admin() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            admin_token: Some("secret".to_string()),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        let e = post_self_test_handler(State(app_state.clone()), HeaderMap::new())
+            .await
+            .unwrap_err();
+        assert_eq!(e.status_code, StatusCode::UNAUTHORIZED);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret"),
+        );
+        let (status, Json(result)) = post_self_test_handler(State(app_state), headers)
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(result.passed);
+    }
+
+    #[tokio::test]
+    async fn test_inverted_window_is_rejected() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let params = GetObservationsParams {
+            observation_end: Some("2023-01-01".parse().unwrap()),
+            ..single_day("2023-01-05")
+        };
+        let e = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_warm_cache_reports_rows_written() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-03", "3"),
+                    ("2023-01-04", "4"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let warm = || {
+            post_cache_warm_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                Query(WarmCacheParams {
+                    series_id: "SP500".to_string(),
+                    observation_end: Some("2023-01-04".parse().unwrap()),
+                    ..Default::default()
+                }),
+            )
+        };
+        assert_eq!(warm().await.unwrap().rows_written, 2);
+        // already cached, so FRED isn't asked again
+        assert_eq!(warm().await.unwrap().rows_written, 0);
+    }
+
+    /// FRED `series` or `tags/series` response body listing series with the given IDs
+    fn fred_seriess(series_ids: &[&str]) -> serde_json::Value {
+        serde_json::json!({
+            "realtime_start": "2023-09-19",
+            "realtime_end": "2023-09-19",
+            "seriess": series_ids
+                .iter()
+                .map(|id| serde_json::json!({
+                    "id": id,
+                    "realtime_start": "2023-09-19",
+                    "realtime_end": "2023-09-19",
+                    "title": id,
+                    "observation_start": "2013-09-19",
+                    "observation_end": "2023-09-18",
+                    "frequency": "Daily",
+                    "frequency_short": "D",
+                    "units": "Index",
+                    "units_short": "Index",
+                    "seasonal_adjustment": "Not Seasonally Adjusted",
+                    "seasonal_adjustment_short": "NSA",
+                    "last_updated": "2023-09-18 19:10:56-05",
+                    "popularity": 1,
+                    "notes": "",
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_tags_series_match_any_unions_per_tag_results() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        for (tag_names, series_ids) in [
+            ("gdp", &["GDP", "GDPC1"][..]),
+            ("usa", &["GDPC1", "UNRATE"][..]),
+            ("gdp;usa", &["GDPC1"][..]),
+        ] {
+            Mock::given(method("GET"))
+                .and(path("/fred/tags/series"))
+                .and(query_param("tag_names", tag_names))
+                .respond_with(ResponseTemplate::new(200).set_body_json(fred_seriess(series_ids)))
+                .mount(&fred)
+                .await;
+        }
+        let tags_series = |tag_match| {
+            get_tags_series_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                Query(GetTagsSeriesParams {
+                    tag_names: "gdp, usa".to_string(),
+                    tag_match,
+                }),
+            )
+        };
+        let ids = |series: Vec<FredEconomicDataSeries>| {
+            series.into_iter().map(|s| s.id).collect::<Vec<_>>()
+        };
+        let Json(any) = tags_series(TagMatch::Any).await.unwrap();
+        assert_eq!(ids(any), vec!["GDP", "GDPC1", "UNRATE"]);
+        let Json(all) = tags_series(TagMatch::All).await.unwrap();
+        assert_eq!(ids(all), vec!["GDPC1"]);
+    }
+
+    #[tokio::test]
+    async fn test_series_fetch_policy() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            series_fetch_policy: SeriesFetchPolicy::CacheOnly,
+            ..test_app_state(&fred, &db_dir).await
+        };
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_seriess(&["SP500"])))
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let e = fetch_series(&app_state, "SP500").await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::NOT_FOUND);
+        let cache_first = AppState {
+            series_fetch_policy: SeriesFetchPolicy::CacheFirst,
+            ..app_state.clone()
+        };
+        // the first fetch fills the cache, which serves the second
+        for _ in 0..2 {
+            let series = fetch_series(&cache_first, "SP500").await.unwrap();
+            assert_eq!(series.units, "Index");
+        }
+        let series = fetch_series(&app_state, "SP500").await.unwrap();
+        assert_eq!(series.title, "SP500");
+    }
+
+    #[tokio::test]
+    async fn test_series_etag() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let mut updated = fred_seriess(&["SP500"]);
+        updated["seriess"][0]["last_updated"] = "2023-09-19 19:10:56-05".into();
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_seriess(&["SP500"])))
+            .up_to_n_times(2)
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(updated))
+            .mount(&fred)
+            .await;
+        let request = |if_none_match: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(tag) = if_none_match {
+                headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(tag).unwrap());
+            }
+            get_series_handler(
+                State(app_state.clone()),
+                headers,
+                Query(GetSeriesParams {
+                    series_id: "SP500".to_string(),
+                    raw: false,
+                }),
+            )
+        };
+        let response = request(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let response = request(Some(&format!("\"other\", W/{etag}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty());
+        // once FRED updates the series, the old tag no longer matches
+        let response = request(Some(&etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_observations_at_dates() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-03", "3"),
+                    observation("2023-01-04", "4"),
+                    observation("2023-01-06", "6"),
+                ],
+            )
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let response = post_observations_at_handler(
+            State(app_state),
+            HeaderMap::new(),
+            Json(GetObservationsAtParams {
+                series_id: "SP500".to_string(),
+                dates: ["2023-01-06", "2023-01-05", "2023-01-03"]
+                    .iter()
+                    .map(|date| date.parse().unwrap())
+                    .collect(),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.headers()[DATA_SOURCE_HEADER], "cache");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let observations_at: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let values: Vec<Option<&str>> = observations_at
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o["value"].as_str())
+            .collect();
+        assert_eq!(values, vec![Some("6"), None, Some("3")]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_dates_do_not_leave_cache_incomplete() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        // FRED counts both rows for 2023-01-04, but only one is cached
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-03", "3"),
+                    ("2023-01-04", "4"),
+                    ("2023-01-04", "4.1"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            ..single_day("2023-01-04")
+        };
+        for _ in 0..2 {
+            let (observations, _) = fetch_observations(&app_state, &params, &Default::default())
+                .await
+                .unwrap();
+            assert_eq!(dates(&observations), vec!["2023-01-03", "2023-01-04"]);
+        }
+        assert!(!app_state
+            .realtime_observations_db
+            .is_missing_observations("SP500")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_min_observations_to_cache_counts_the_whole_series() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            min_observations_to_cache: 3,
+            ..test_app_state(&fred, &db_dir).await
+        };
+        let db = &app_state.realtime_observations_db;
+        // a year of a large series: FRED's count of the whole series decides
+        let mut whole_series = fred_observations(&[("2023-01-03", "3")]);
+        whole_series["count"] = 2500.into();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("series_id", "SP500"))
+            .and(query_param("sort_order", "desc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(whole_series))
+            .expect(1)
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("series_id", "SP500"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[("2023-01-03", "3")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        fetch_observations(&app_state, &single_day("2023-01-03"), &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(db.count_observations("SP500").await.unwrap(), 1);
+        // all of a small series, which is known to be small without asking
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("series_id", "GDPA"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2021-01-01", "23594.0"),
+                    ("2022-01-01", "25744.1"),
+                ])),
+            )
+            .expect(2)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            series_id: "GDPA".to_string(),
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            fetch_observations(&app_state, &params, &Default::default())
+                .await
+                .unwrap();
+        }
+        assert_eq!(db.count_observations("GDPA").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_worth_caching_asks_fred_only_when_it_matters() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let timing = ServerTiming::default();
+        // without a threshold, and with nothing fetched to cache
+        assert!(is_worth_caching(&app_state, &timing, "SP500", 0, false)
+            .await
+            .unwrap());
+        let app_state = AppState {
+            min_observations_to_cache: 3,
+            ..app_state
+        };
+        assert!(!is_worth_caching(&app_state, &timing, "SP500", 0, false)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_empty_series_is_not_refetched() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_observations(&[])))
+            .expect(1)
+            .mount(&fred)
+            .await;
+        for _ in 0..2 {
+            let (observations, source) =
+                fetch_observations(&app_state, &single_day("2023-01-03"), &Default::default())
+                    .await
+                    .unwrap();
+            assert!(observations.is_empty());
+            assert_eq!(source, DataSource::Empty);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observations_end_to_end_against_fred_base_url() {
+        use tower::ServiceExt;
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let cli = CommandLineInterface::try_parse_from([
+            "stlouisfed-fred-web-proxy",
+            "--sqlite-db",
+            db_dir.path().join("cache.db").to_str().unwrap(),
+            "--fred-api-key",
+            "abcdefghijklmnopqrstuvwxyz012345",
+            "--fred-base-url",
+            &fred.uri(),
+        ])
+        .unwrap();
+        cli.validate().unwrap();
+        let app_state = AppState {
+            fred: fred_client(&cli).unwrap(),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("api_key", "abcdefghijklmnopqrstuvwxyz012345"))
+            .and(query_param("series_id", "SP500"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-03", "3"),
+                    ("2023-01-04", "4"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let app = Router::new()
+            .route("/v0/observations", get(get_observations_handler))
+            .with_state(app_state);
+        // the second request is answered from what the first cached
+        for expected_source in ["upstream", "cache"] {
+            let response = app
+                .clone()
+                .oneshot(
+                    axum::http::Request::get(
+                        "/v0/observations?series_id=SP500&observation_start=2023-01-03&observation_end=2023-01-04",
+                    )
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[DATA_SOURCE_HEADER], expected_source);
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(
+                body,
+                r#"[{"date":"2023-01-03","value":"3"},{"date":"2023-01-04","value":"4"}]"#
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_access_log_line() {
+        use tower::ServiceExt;
+        let log_dir = tempfile::tempdir().unwrap();
+        let log_path = log_dir.path().join("access.ndjson");
+        let app = Router::new()
+            .route(
+                "/v0/observations",
+                get(|| async { with_data_source("[]".into_response(), DataSource::Cache) }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                access_log::AccessLog::open(&log_path).unwrap(),
+                access_log::record,
+            ));
+        let response = app
+            .oneshot(
+                axum::http::Request::get("/v0/observations?series_id=SP500")
+                    .header("x-request-id", "abc")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-request-id"], "abc");
+        let line: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&log_path).unwrap()).unwrap();
+        assert_eq!(line["request_id"], "abc");
+        assert_eq!(line["method"], "GET");
+        assert_eq!(line["path"], "/v0/observations");
+        assert_eq!(line["series_id"], "SP500");
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["bytes"], 2);
+        assert_eq!(line["cache"], "cache");
+    }
+
+    #[test]
+    fn test_diff_observations() {
+        let live = [
+            observation("2023-01-02", "2"),
+            observation("2023-01-03", "3.5"),
+            observation("2023-01-05", "5"),
+        ];
+        let cached = [
+            observation("2023-01-01", "1"),
+            observation("2023-01-03", "3"),
+            observation("2023-01-04", "4"),
+            observation("2023-01-05", "5"),
+        ];
+        let diff = diff_observations(&live, &cached);
+        assert_eq!(dates_of(&diff.differing_dates), vec!["2023-01-03"]);
+        assert_eq!(diff.missing_in_cache, 1);
+        assert_eq!(diff.extra_in_cache, 2);
+        assert_eq!(diff_observations(&live, &live), CacheDiff::default());
+    }
+
+    fn dates_of(dates: &[NaiveDate]) -> Vec<String> {
+        dates.iter().map(|date| date.to_string()).collect()
+    }
+
+    #[test]
+    fn test_render_observations_as_map() {
+        let params = GetObservationsParams {
+            format: ObservationsFormat::Map,
+            ..single_day("2023-01-03")
+        };
+        let observations = [
+            observation("2023-01-03", "3"),
+            observation("2023-01-04", "."),
+            observation("2023-01-04", "4"),
+        ];
+        let (content_type, body) = render_observations(&observations, &params).unwrap();
+        assert_eq!(content_type, "application/json");
+        let map: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            map,
+            serde_json::json!({"2023-01-03": "3", "2023-01-04": "4"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_shedding() {
+        use tower::ServiceExt;
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel::<()>();
+        let started_tx = Arc::new(std::sync::Mutex::new(Some(started_tx)));
+        let release = Arc::new(tokio::sync::Notify::new());
+        let app = with_load_shedding(
+            Router::new()
+                .route(
+                    "/",
+                    get({
+                        let release = release.clone();
+                        move || async move {
+                            if let Some(started_tx) = started_tx.lock().unwrap().take() {
+                                started_tx.send(()).unwrap();
+                            }
+                            release.notified().await;
+                        }
+                    }),
+                )
+                .route("/other", get(|| async {})),
+            1,
+        );
+        let request = |path| {
+            axum::http::Request::get(path)
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+        let first = tokio::spawn(app.clone().oneshot(request("/")));
+        started_rx.await.unwrap();
+        // the limit covers all routes together
+        let shed = app.clone().oneshot(request("/other")).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+        release.notify_one();
+        assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_latest() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-02", "2"),
+                    observation("2023-01-03", "3"),
+                    observation("2023-01-09", "9"),
+                ],
+            )
+            .await
+            .unwrap();
+        let params = GetObservationsParams {
+            observation_end: Some("2023-01-06".parse().unwrap()),
+            fallback_to_latest: true,
+            ..single_day("2023-01-04")
+        };
+        let (observations, source) =
+            fallback_to_latest(&app_state, &params, (Vec::new(), DataSource::Cache))
+                .await
+                .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-03"]);
+        assert_eq!(source, DataSource::LatestBeforeRange);
+        let fetched = (vec![observation("2023-01-05", "5")], DataSource::Upstream);
+        let (observations, source) = fallback_to_latest(&app_state, &params, fetched)
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-05"]);
+        assert_eq!(source, DataSource::Upstream);
+    }
+
+    #[tokio::test]
+    async fn test_past_vintage_is_cached_apart() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            vintage_cache: true,
+            ..test_app_state(&fred, &db_dir).await
+        };
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("realtime_end", "2023-02-01"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-03", "3"),
+                    ("2023-01-04", "4"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            realtime_start: Some("2023-02-01".parse().unwrap()),
+            realtime_end: Some("2023-02-01".parse().unwrap()),
+            ..single_day("2023-01-04")
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-04"]);
+        assert_eq!(source, DataSource::Upstream);
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-04"]);
+        assert_eq!(source, DataSource::Cache);
+        let current = app_state
+            .realtime_observations_db
+            .get_observations("SP500", None, None)
+            .await
+            .unwrap();
+        assert!(current.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_page_through_observations() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-03", "3"),
+                    observation("2023-01-04", "4"),
+                    observation("2023-01-05", "5"),
+                ],
+            )
+            .await
+            .unwrap();
+        let params = GetObservationsParams {
+            observation_end: Some("2023-01-05".parse().unwrap()),
+            page_size: Some(2),
+            ..single_day("2023-01-03")
+        };
+        let (mut observations, _) = fetch_observations(
+            &app_state,
+            &page_params(&params).unwrap(),
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        let cursor = paginate(&mut observations, 2);
+        assert_eq!(dates(&observations), vec!["2023-01-03", "2023-01-04"]);
+        assert!(cursor.is_some());
+        let params = GetObservationsParams { cursor, ..params };
+        let (mut observations, _) = fetch_observations(
+            &app_state,
+            &page_params(&params).unwrap(),
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(paginate(&mut observations, 2), None);
+        assert_eq!(dates(&observations), vec!["2023-01-05"]);
+        let invalid = GetObservationsParams {
+            cursor: Some("2023-01-04".to_string()),
+            ..params
+        };
+        let e = page_params(&invalid).unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_search_results_are_cached() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            search_cache_secs: Some(60),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        Mock::given(method("GET"))
+            .and(path("/fred/series/search/related_tags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "realtime_start": "2023-03-01",
+                "realtime_end": "2023-03-01",
+                "order_by": "series_count",
+                "sort_order": "desc",
+                "count": 0,
+                "offset": 0,
+                "limit": 1000,
+                "tags": []
+            })))
+            .expect(1)
+            .mount(&fred)
+            .await;
+        for (series_search_text, tag_names, source) in [
+            ("mortgage rate", "30-year;frb", "upstream"),
+            ("Mortgage  Rate", "frb; 30-year", "cache"),
+        ] {
+            let params = GetSeriesSearchRelatedTagsParams {
+                series_search_text: series_search_text.to_string(),
+                tag_names: tag_names.to_string(),
+                exclude_tag_names: None,
+                limit: None,
+                offset: None,
+            };
+            let response = get_series_search_related_tags_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                Query(params),
+            )
+            .await
+            .unwrap();
+            assert_eq!(response.headers()[DATA_SOURCE_HEADER], source);
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let tags: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(tags["limit"], 1000);
+        }
+    }
+
+    #[test]
+    fn test_observation_summary_headers() {
+        let summary = ObservationSummary::of(&[
+            observation("2023-01-03", "3"),
+            observation("2023-01-04", "."),
+        ]);
+        let response = with_observation_summary(().into_response(), &summary);
+        let header = |name| response.headers().get(name).map(|v| v.to_str().unwrap());
+        assert_eq!(header(OBS_MIN_HEADER), Some("3"));
+        assert_eq!(header(OBS_MAX_HEADER), Some("3"));
+        assert_eq!(header(OBS_LAST_HEADER), Some("3"));
+        assert_eq!(header(OBS_COUNT_HEADER), Some("1"));
+        let response = with_observation_summary(().into_response(), &Default::default());
+        assert!(response.headers().get(OBS_LAST_HEADER).is_none());
+        assert_eq!(response.headers()[OBS_COUNT_HEADER], "0");
+    }
+
+    #[tokio::test]
+    async fn test_series_allowlist() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            series_allowlist: Some(Arc::new(HashSet::from(["GDP".to_string()]))),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        // no mock is mounted, so reaching FRED would fail differently
+        let e = fetch_observations(&app_state, &single_day("2023-01-04"), &Default::default())
+            .await
+            .unwrap_err();
+        assert_eq!(e.status_code, StatusCode::FORBIDDEN);
+        let e = fetch_series(&app_state, "SP500").await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::FORBIDDEN);
+        assert!(fred.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_right_edge_grace_days() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            right_edge_grace_days: 3,
+            ..test_app_state(&fred, &db_dir).await
+        };
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-02", "2"),
+                    observation("2023-01-03", "3"),
+                ],
+            )
+            .await
+            .unwrap();
+        let params = GetObservationsParams {
+            observation_end: Some("2023-01-06".parse().unwrap()),
+            ..single_day("2023-01-02")
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-02", "2023-01-03"]);
+        assert_eq!(source, DataSource::Cache);
+        assert!(fred.received_requests().await.unwrap().is_empty());
+        assert!(
+            !app_state.covers_through("2023-01-03".parse().unwrap(), "2023-01-07".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_records_last_fetch_error() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            record_fetch_errors: true,
+            ..test_app_state(&fred, &db_dir).await
+        };
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error_code": 400,
+                "error_message": "Bad Request.  The series does not exist.",
+            })))
+            .mount(&fred)
+            .await;
+        fetch_observations(&app_state, &single_day("2023-01-04"), &Default::default())
+            .await
+            .unwrap_err();
+        let Json(errors) = get_fetch_errors_handler(State(app_state)).await.unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].series_id, "SP500");
+        assert_eq!(errors[0].status, 400);
+        assert_eq!(
+            errors[0].message.as_deref(),
+            Some("Bad Request.  The series does not exist.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upstream_status_is_cached() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_seriess(&["GNPCA"])))
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let Json(status) = get_upstream_status_handler(State(app_state.clone())).await;
+        assert!(status.reachable);
+        assert!(status.error.is_none());
+        let Json(again) = get_upstream_status_handler(State(app_state)).await;
+        assert_eq!(again.checked_at, status.checked_at);
+    }
+
+    #[tokio::test]
+    async fn test_fills_gap_before_cached_observations() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        // a backfill that never got to 2023-01-03
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2023-01-04", "4"),
+                observation("2023-01-05", "5"),
+            ],
+        )
+        .await
+        .unwrap();
+        db.put_observation_count("SP500", None, Some("2023-01-05".parse().unwrap()), 3)
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_end", "2023-01-03"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[("2023-01-03", "3")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        // no refetch of the whole range
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            ..single_day("2023-01-05")
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(source, DataSource::Merged);
+        assert_eq!(
+            dates(&observations),
+            vec!["2023-01-03", "2023-01-04", "2023-01-05"]
+        );
+        assert!(!db.is_missing_observations("SP500").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_fills_gap_after_cached_observations() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        // a write that stopped before 2023-01-05
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2023-01-03", "3"),
+                observation("2023-01-04", "4"),
+            ],
+        )
+        .await
+        .unwrap();
+        db.put_observation_count("SP500", None, Some("2023-01-05".parse().unwrap()), 3)
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_start", "2023-01-05"))
+            .and(query_param("observation_end", "2023-01-05"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[("2023-01-05", "5")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        // no refetch of the whole range
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            ..single_day("2023-01-05")
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(source, DataSource::Merged);
+        assert_eq!(
+            dates(&observations),
+            vec!["2023-01-03", "2023-01-04", "2023-01-05"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fills_gaps_on_both_sides_of_cached_observations() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        db.put_observations("SP500", &[observation("2023-01-04", "4")])
+            .await
+            .unwrap();
+        db.put_observation_count("SP500", None, Some("2023-01-05".parse().unwrap()), 3)
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_start", "2023-01-03"))
+            .and(query_param("observation_end", "2023-01-03"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[("2023-01-03", "3")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_start", "2023-01-05"))
+            .and(query_param("observation_end", "2023-01-05"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[("2023-01-05", "5")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            ..single_day("2023-01-05")
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(source, DataSource::Merged);
+        assert_eq!(
+            dates(&observations),
+            vec!["2023-01-03", "2023-01-04", "2023-01-05"]
+        );
+        assert!(!db.is_missing_observations("SP500").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deduplicates_observations_fred_returns_for_cached_dates() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2023-01-03", "3"),
+                observation("2023-01-04", "4"),
+            ],
+        )
+        .await
+        .unwrap();
+        db.put_observation_count("SP500", None, Some("2023-01-05".parse().unwrap()), 3)
+            .await
+            .unwrap();
+        // FRED answers for the day after the cache with the last cached day too, revised, which
+        // replaces the cached value under the default duplicate policy
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_start", "2023-01-05"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                    ("2023-01-04", "4.1"),
+                    ("2023-01-05", "5"),
+                ])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            ..single_day("2023-01-05")
+        };
+        let (observations, _) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            dates(&observations),
+            vec!["2023-01-03", "2023-01-04", "2023-01-05"]
+        );
+        assert_eq!(observations[1].value, "4.1");
+        let cached = db.get_observations("SP500", None, None).await.unwrap();
+        assert_eq!(
+            dates(&cached),
+            vec!["2023-01-03", "2023-01-04", "2023-01-05"]
+        );
+        assert_eq!(cached[1].value, "4.1");
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_policy_applies_to_merged_and_cached_observations() {
+        for (duplicate_policy, value) in [
+            (DuplicatePolicy::LatestRealtime, "4.1"),
+            (DuplicatePolicy::FirstSeen, "4"),
+        ] {
+            let fred = MockServer::start().await;
+            let db_dir = tempfile::tempdir().unwrap();
+            let app_state = test_app_state(&fred, &db_dir).await;
+            let app_state = AppState {
+                realtime_observations_db: app_state
+                    .realtime_observations_db
+                    .clone()
+                    .with_duplicate_policy(duplicate_policy),
+                ..app_state
+            };
+            app_state
+                .realtime_observations_db
+                .put_observations("SP500", &[observation("2023-01-04", "4")])
+                .await
+                .unwrap();
+            // FRED answers for the day after the cache with the cached day too, revised
+            Mock::given(method("GET"))
+                .and(path("/fred/series/observations"))
+                .and(query_param("observation_start", "2023-01-05"))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(fred_observations(&[
+                        ("2023-01-04", "4.1"),
+                        ("2023-01-05", "5"),
+                    ])),
+                )
+                .expect(1)
+                .mount(&fred)
+                .await;
+            let params = GetObservationsParams {
+                observation_start: Some("2023-01-04".parse().unwrap()),
+                ..single_day("2023-01-05")
+            };
+            // the second request is a cache hit
+            for _ in 0..2 {
+                let (observations, _) =
+                    fetch_observations(&app_state, &params, &Default::default())
+                        .await
+                        .unwrap();
+                assert_eq!(dates(&observations), vec!["2023-01-04", "2023-01-05"]);
+                assert_eq!(observations[0].value, value, "{duplicate_policy:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remembers_ranges_before_series_begins() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        db.put_observations("SP500", &[observation("2013-09-19", "1725.52")])
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_end", "1950-12-31"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_observations(&[])))
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("1950-01-01".parse().unwrap()),
+            ..single_day("1950-12-31")
+        };
+        for _ in 0..2 {
+            let (observations, _) = fetch_observations(&app_state, &params, &Default::default())
+                .await
+                .unwrap();
+            assert!(observations.is_empty());
+        }
+        let within = GetObservationsParams {
+            observation_start: Some("1950-06-01".parse().unwrap()),
+            ..single_day("1950-06-30")
+        };
+        let (observations, source) = fetch_observations(&app_state, &within, &Default::default())
+            .await
+            .unwrap();
+        assert!(observations.is_empty());
+        assert_eq!(source, DataSource::Empty);
+    }
+
+    #[tokio::test]
+    async fn test_remembers_nothing_after_cached_observations() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations("SP500", &[observation("2023-01-03", "3")])
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_start", "2023-01-04"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_observations(&[])))
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            ..single_day("2023-01-08")
+        };
+        fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(dates(&observations), vec!["2023-01-03"]);
+        assert_eq!(source, DataSource::Cache);
+    }
+
+    #[tokio::test]
+    async fn test_fills_hole_among_cached_observations() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        // a monthly series missing March
+        db.put_observations(
+            "UNRATE",
+            &[
+                observation("2023-01-01", "3.4"),
+                observation("2023-02-01", "3.6"),
+                observation("2023-04-01", "3.4"),
+            ],
+        )
+        .await
+        .unwrap();
+        db.put_observation_count("UNRATE", None, Some("2023-04-01".parse().unwrap()), 4)
+            .await
+            .unwrap();
+        let mut series = fred_seriess(&["UNRATE"]);
+        series["seriess"][0]["frequency_short"] = "M".into();
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(series))
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_start", "2023-02-02"))
+            .and(query_param("observation_end", "2023-03-31"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(fred_observations(&[("2023-03-01", "3.5")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        // no refetch of the whole range
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            series_id: "UNRATE".to_string(),
+            observation_start: Some("2023-01-01".parse().unwrap()),
+            observation_end: Some("2023-04-01".parse().unwrap()),
+            ..Default::default()
+        };
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(source, DataSource::Merged);
+        assert_eq!(
+            dates(&observations),
+            vec!["2023-01-01", "2023-02-01", "2023-03-01", "2023-04-01"]
+        );
+        assert!(!db.is_missing_observations("UNRATE").await.unwrap());
+    }
+
+    #[test]
+    fn test_holes_need_a_regular_frequency() {
+        let observations = [
+            observation("2023-01-02", "1"),
+            observation("2023-01-09", "2"),
+            observation("2023-01-23", "4"),
+        ];
+        assert_eq!(
+            holes(&observations, Some(Frequency::Weekly)),
+            Some(vec![(
+                "2023-01-10".parse().unwrap(),
+                "2023-01-22".parse().unwrap()
+            )])
+        );
+        assert_eq!(holes(&observations, Some(Frequency::Daily)), None);
+        assert_eq!(holes(&observations, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_include_realtime_from_cache() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[("2023-01-03", "3")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            include_realtime: true,
+            ..single_day("2023-01-03")
+        };
+        fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        let (observations, source) = fetch_observations(&app_state, &params, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(source, DataSource::Cache);
+        // the day of the fetch, which FRED stamps observations fetched without a realtime period
+        let (_, body) = render_observations(&observations, &params).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!([{
+                "date": "2023-01-03",
+                "value": "3",
+                "realtime_start": "2023-09-19",
+                "realtime_end": "2023-09-19",
+            }])
+        );
+        let (_, body) = render_observations(&observations, &single_day("2023-01-03")).unwrap();
+        assert_eq!(body, br#"[{"date":"2023-01-03","value":"3"}]"#);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_tags_on_error() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/tags"))
+            .and(query_param("series_id", "GONE"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error_code": 400,
+                "error_message": "Bad Request.  The series does not exist.",
+            })))
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/tags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "count": 1, "offset": 0, "limit": 1000,
+                "tags": [{"name": "usa", "group_id": "geo", "notes": null, "created": "2012-02-27 10:18:19-06", "popularity": 100, "series_count": 1}],
+            })))
+            .mount(&fred)
+            .await;
+        let aggregate = |on_error| {
+            post_series_tags_aggregate_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                Query(AggregateSeriesTagsQuery { on_error }),
+                Json(AggregateSeriesTagsParams {
+                    series_ids: vec!["SP500".to_string(), "GONE".to_string()],
+                }),
+            )
+        };
+        let (status, Json(aggregated)) = aggregate(OnError::Partial).await.unwrap();
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert_eq!(aggregated.tags.len(), 1);
+        assert_eq!(aggregated.errors.keys().collect::<Vec<_>>(), ["GONE"]);
+        let e = aggregate(OnError::Fail).await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_if_modified_since_updated() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_seriess(&["SP500"])))
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(fred_observations(&[("2023-09-18", "4453.53")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        // FRED last updated SP500 at 2023-09-18 19:10:56-05
+        let observations_since = |since: &str| {
+            get_observations_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                RawQuery(None),
+                Query(GetObservationsParams {
+                    if_modified_since_updated: Some(
+                        chrono::DateTime::parse_from_rfc3339(since)
+                            .unwrap()
+                            .with_timezone(&chrono::Utc),
+                    ),
+                    ..single_day("2023-09-18")
+                }),
+            )
+        };
+        let response = observations_since("2023-09-19T00:10:56Z").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let response = observations_since("2023-09-18T00:00:00Z").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_raw_observations() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let mut body = fred_observations(&[("2023-09-18", "4453.53")]);
+        body["units"] = "lin".into();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body.clone()))
+            .expect(2)
+            .mount(&fred)
+            .await;
+        let raw = |params| {
+            get_observations_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                RawQuery(None),
+                Query(params),
+            )
+        };
+        let params = GetObservationsParams {
+            raw: true,
+            ..single_day("2023-09-18")
+        };
+        // never cached, so both requests go to FRED
+        for _ in 0..2 {
+            let response = raw(params.clone()).await.unwrap();
+            let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(),
+                body
+            );
+        }
+        let e = raw(GetObservationsParams {
+            round: Some(2),
+            ..params
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_upstream_http_client_min_tls_version() {
+        assert!(upstream_http_client(MinTlsVersion::Tls12).is_ok());
+        assert!(upstream_http_client(MinTlsVersion::Tls13).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_observation_start_clamped_to_series_start() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        // SP500's observations start 2013-09-19
+        let series: FredEconomicDataSeries =
+            serde_json::from_value(fred_seriess(&["SP500"])["seriess"][0].clone()).unwrap();
+        db.put_series(&series).await.unwrap();
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2013-09-19", "1722.34"),
+                observation("2013-09-20", "1709.91"),
+            ],
+        )
+        .await
+        .unwrap();
+        db.put_observation_count("SP500", None, Some("2013-09-20".parse().unwrap()), 2)
+            .await
+            .unwrap();
+        // nothing asked about the dates before 2013-09-19
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let response = get_observations_handler(
+            State(app_state),
+            HeaderMap::new(),
+            RawQuery(None),
+            Query(GetObservationsParams {
+                observation_start: Some("1900-01-01".parse().unwrap()),
+                ..single_day("2013-09-20")
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.headers()[header::WARNING],
+            "199 - \"observation_start 1900-01-01 is before SP500 begins; its observations start 2013-09-19\""
+        );
+    }
+
+    #[tokio::test]
+    async fn test_precompresses_only_responses_covering_observation_end() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = AppState {
+            precompressed_responses: PrecompressedResponses::new(8),
+            ..test_app_state(&fred, &db_dir).await
+        };
+        let db = &app_state.realtime_observations_db;
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2023-01-03", "3"),
+                observation("2023-01-04", "4"),
+                observation("2023-01-05", "5"),
+            ],
+        )
+        .await
+        .unwrap();
+        // FRED said there was nothing after the cache, which is only trusted for a while
+        db.record_empty_range(
+            "SP500",
+            Some("2023-01-06".parse().unwrap()),
+            None,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&fred)
+            .await;
+        let get = |query: &str, observation_end: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+            get_observations_handler(
+                State(app_state.clone()),
+                headers,
+                RawQuery(Some(query.to_string())),
+                Query(GetObservationsParams {
+                    series_id: "SP500".to_string(),
+                    observation_end: observation_end.map(|date| date.parse().unwrap()),
+                    ..Default::default()
+                }),
+            )
+        };
+        let response = get("series_id=SP500", None).await.unwrap();
+        assert_eq!(response.headers()[DATA_SOURCE_HEADER], "cache");
+        assert!(app_state
+            .precompressed_responses
+            .get("SP500", "series_id=SP500")
+            .is_none());
+        let query = "series_id=SP500&observation_end=2023-01-05";
+        let response = get(query, Some("2023-01-05")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert!(app_state
+            .precompressed_responses
+            .get("SP500", &normalized_query(query))
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sort_order_desc() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-03", "3"),
+                    observation("2023-01-04", "4"),
+                    observation("2023-01-05", "5"),
+                ],
+            )
+            .await
+            .unwrap();
+        let request = |query: &str| {
+            let uri = format!(
+                "/v0/observations?series_id=SP500&observation_start=2023-01-03&observation_end=2023-01-05&sort_order=desc{query}"
+            )
+            .parse()
+            .unwrap();
+            let Query(params) = Query::<GetObservationsParams>::try_from_uri(&uri).unwrap();
+            get_observations_handler(
+                State(app_state.clone()),
+                HeaderMap::new(),
+                RawQuery(None),
+                Query(params),
+            )
+        };
+        let response = request("").await.unwrap();
+        // the summary headers don't depend on the order
+        assert_eq!(response.headers()[OBS_LAST_HEADER], "5");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let observations: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let dates: Vec<_> = observations
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|observation| observation["date"].as_str().unwrap())
+            .collect();
+        assert_eq!(dates, ["2023-01-05", "2023-01-04", "2023-01-03"]);
+        let response = request("&tail=2&format=csv").await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "date,value\n2023-01-05,5\n2023-01-04,4\n");
+        let e = request("&page_size=2").await.unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_limit_and_offset() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-01-03", "3"),
+                    observation("2023-01-04", "4"),
+                    observation("2023-01-05", "5"),
+                    observation("2023-01-06", "6"),
+                ],
+            )
+            .await
+            .unwrap();
+        let request = |query: &str| {
+            let uri = format!(
+                "/v0/observations?series_id=SP500&observation_start=2023-01-03&observation_end=2023-01-06&{query}"
+            )
+            .parse()
+            .unwrap();
+            let Query(params) = Query::<GetObservationsParams>::try_from_uri(&uri).unwrap();
+            async {
+                let response = get_observations_handler(
+                    State(app_state.clone()),
+                    HeaderMap::new(),
+                    RawQuery(None),
+                    Query(params),
+                )
+                .await?;
+                let total = response.headers()[TOTAL_COUNT_HEADER]
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                let observations: Vec<RealtimeObservation> = serde_json::from_slice(&body).unwrap();
+                let values: Vec<_> = observations.into_iter().map(|o| o.value).collect();
+                Ok::<_, FredApiError>((values, total))
+            }
+        };
+        assert_eq!(
+            request("limit=2&offset=1").await.unwrap(),
+            (vec!["4".to_string(), "5".to_string()], "4".to_string())
+        );
+        assert_eq!(
+            request("limit=2&offset=1&sort_order=desc").await.unwrap(),
+            (vec!["5".to_string(), "4".to_string()], "4".to_string())
+        );
+        assert_eq!(
+            request("offset=3").await.unwrap(),
+            (vec!["6".to_string()], "4".to_string())
+        );
+        // past the end, the window is empty but the total still tells how many there are
+        assert_eq!(
+            request("offset=10&limit=5").await.unwrap(),
+            (vec![], "4".to_string())
+        );
+        for query in ["limit=0", "limit=100001", "limit=2&page_size=2"] {
+            let e = request(query).await.unwrap_err();
+            assert_eq!(e.status_code, StatusCode::BAD_REQUEST, "{query}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_series_overview() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_seriess(&["SP500"])))
+            .mount(&fred)
+            .await;
+        // only the overviews without the whole series cached ask FRED for the latest observation
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("sort_order", "desc"))
+            .and(query_param("limit", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json({
+                let mut latest = fred_observations(&[("2023-09-18", "4453.53")]);
+                latest["count"] = 2500.into();
+                latest
+            }))
+            .expect(2)
+            .mount(&fred)
+            .await;
+        let overview = |app_state: AppState| async move {
+            let response = get_series_overview_handler(
+                State(app_state),
+                HeaderMap::new(),
+                Query(GetSeriesOverviewParams {
+                    series_id: "SP500".to_string(),
+                }),
+            )
+            .await
+            .unwrap();
+            let source = response.headers()[DATA_SOURCE_HEADER].clone();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            (
+                source,
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            )
+        };
+        let (source, body) = overview(app_state.clone()).await;
+        assert_eq!(source, "upstream");
+        assert_eq!(body["metadata"]["title"], "SP500");
+        assert_eq!(body["latest"]["date"], "2023-09-18");
+        assert_eq!(body["latest"]["value"], "4453.53");
+        assert_eq!(body["first_date"], "2013-09-19");
+        assert_eq!(body["observation_count"], 2500);
+
+        app_state
+            .realtime_observations_db
+            .put_observations(
+                "SP500",
+                &[
+                    observation("2023-09-15", "4450.32"),
+                    observation("2023-09-18", "4453.53"),
+                ],
+            )
+            .await
+            .unwrap();
+        // only a window of the series, which is no count of all of it
+        app_state
+            .realtime_observations_db
+            .put_observation_count("SP500", Some("2023-09-15".parse().unwrap()), None, 2)
+            .await
+            .unwrap();
+        let (source, body) = overview(app_state.clone()).await;
+        assert_eq!(source, "upstream");
+        assert_eq!(body["observation_count"], 2500);
+        app_state
+            .realtime_observations_db
+            .put_observation_count("SP500", None, None, 2)
+            .await
+            .unwrap();
+        let (source, body) = overview(app_state).await;
+        assert_eq!(source, "merged");
+        assert_eq!(body["latest"]["date"], "2023-09-18");
+        assert_eq!(body["observation_count"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_series_search() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/search"))
+            .and(query_param("search_text", "s&p 500"))
+            .and(query_param("limit", "2"))
+            .and(query_param("order_by", "popularity"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(fred_seriess(&["SP500", "SPASTT01USM661N"])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let search = |search_text: &str| GetSeriesSearchParams {
+            search_text: search_text.to_string(),
+            limit: Some(2),
+            order_by: Some("popularity".to_string()),
+        };
+        let response = get_series_search_handler(
+            State(app_state.clone()),
+            HeaderMap::new(),
+            Query(search("s&p 500")),
+        )
+        .await
+        .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let series: Vec<FredEconomicDataSeries> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            series.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(),
+            ["SP500", "SPASTT01USM661N"]
+        );
+
+        let err = get_series_search_handler(State(app_state), HeaderMap::new(), Query(search(" ")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_accept_text_csv() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        app_state
+            .realtime_observations_db
+            .put_observations("SP500", &[observation("2023-01-03", ".")])
+            .await
+            .unwrap();
+        let request = |accept: &'static str, query: &str| {
+            let app_state = app_state.clone();
+            let query = query.to_string();
+            async move {
+                let headers =
+                    HeaderMap::from_iter([(header::ACCEPT, HeaderValue::from_static(accept))]);
+                let uri = format!("/v0/observations?{query}").parse().unwrap();
+                let params = Query::try_from_uri(&uri).unwrap();
+                let response = get_observations_handler(
+                    State(app_state),
+                    headers,
+                    RawQuery(Some(query)),
+                    params,
+                )
+                .await
+                .unwrap();
+                let content_type = response.headers()[header::CONTENT_TYPE].clone();
+                let vary = response.headers().get(header::VARY).cloned();
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                (
+                    content_type,
+                    vary,
+                    String::from_utf8(body.to_vec()).unwrap(),
+                )
+            }
+        };
+        let query = "series_id=SP500&observation_start=2023-01-03&observation_end=2023-01-03";
+        let (content_type, vary, body) = request("text/csv", query).await;
+        assert_eq!(content_type, "text/csv; charset=utf-8");
+        assert_eq!(vary.unwrap(), "accept");
+        assert_eq!(body, "date,value\n2023-01-03,\n");
+        let (content_type, _, _) = request("application/json, text/csv", query).await;
+        assert_eq!(content_type, "application/json");
+        // an explicit format wins over Accept
+        let (content_type, vary, _) = request("text/csv", &format!("{query}&format=json")).await;
+        assert_eq!(content_type, "application/json");
+        assert!(vary.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let (status, Json(health)) = get_health_handler(State(app_state.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(health.status, "ok");
+        app_state.realtime_observations_db.close().await;
+        let (status, Json(health)) = get_health_handler(State(app_state)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health.status, "unavailable");
+        assert!(fred.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sigterm_finishes_in_flight_requests() {
+        let app = Router::new().route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                "done"
+            }),
+        );
+        let server =
+            axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let url = format!("http://{}/slow", server.local_addr());
+        let server = tokio::spawn(server.with_graceful_shutdown(shutdown_signal().unwrap()));
+        let response = tokio::spawn(reqwest::get(url));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let killed = std::process::Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(killed.success());
+        let response = response.await.unwrap().unwrap();
+        assert_eq!(response.text().await.unwrap(), "done");
+        tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_category() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        Mock::given(method("GET"))
+            .and(path("/fred/category"))
+            .and(query_param("category_id", "125"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "categories": [{"id": 125, "name": "Trade Balance", "parent_id": 13}],
+            })))
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/category"))
+            .and(query_param("category_id", "999999"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error_code": 400,
+                "error_message": "Bad Request.  The category does not exist.",
+            })))
+            .mount(&fred)
+            .await;
+        let Json(categories) = get_category_handler(
+            State(app_state.clone()),
+            HeaderMap::new(),
+            Query(GetCategoryParams { category_id: 125 }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(categories.len(), 1);
+        assert_eq!(categories[0].name, "Trade Balance");
+        assert_eq!(categories[0].parent_id, 13);
+        let e = get_category_handler(
+            State(app_state),
+            HeaderMap::new(),
+            Query(GetCategoryParams {
+                category_id: 999999,
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_include_meta_on_left_side_backfill() {
+        let fred = MockServer::start().await;
+        let db_dir = tempfile::tempdir().unwrap();
+        let app_state = test_app_state(&fred, &db_dir).await;
+        let db = &app_state.realtime_observations_db;
+        // a backfill that never got to 2023-01-03
+        db.put_observations(
+            "SP500",
+            &[
+                observation("2023-01-04", "4"),
+                observation("2023-01-05", "5"),
+            ],
+        )
+        .await
+        .unwrap();
+        db.put_observation_count("SP500", None, Some("2023-01-05".parse().unwrap()), 3)
+            .await
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/fred/series"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fred_seriess(&["SP500"])))
+            .mount(&fred)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fred/series/observations"))
+            .and(query_param("observation_end", "2023-01-03"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fred_observations(&[("2023-01-03", "3")])),
+            )
+            .expect(1)
+            .mount(&fred)
+            .await;
+        let params = GetObservationsParams {
+            observation_start: Some("2023-01-03".parse().unwrap()),
+            include_meta: true,
+            ..single_day("2023-01-05")
+        };
+        let response = get_observations_handler(
+            State(app_state),
+            HeaderMap::new(),
+            RawQuery(None),
+            Query(params),
+        )
         .await
-        .map_err(|_| FredApiError::default())?;
-    let mut observations = cached;
-    observations.extend_from_slice(&fresh_observations);
-    Ok(axum::Json(observations))
+        .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["observations"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            body["meta"],
+            serde_json::json!({
+                "cache_hit": false,
+                "fetched_ranges": [{"start": "2023-01-03", "end": "2023-01-03"}],
+                "source": "mixed",
+            })
+        );
+    }
 }
@@ -0,0 +1,76 @@
+use std::time::Instant;
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
+
+/// Upper bounds, in seconds, of the latency histogram buckets. FRED usually answers in well
+/// under a second, but a long series takes several pages.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Install the process-wide recorder the `metrics` macros report to, returning the handle that
+/// renders what they recorded for `/metrics`.
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets(&LATENCY_BUCKETS)?
+        .install_recorder()
+}
+
+/// `/metrics`, in Prometheus' text format
+pub async fn render(State(handle): State<PrometheusHandle>) -> String {
+    handle.render()
+}
+
+/// Middleware recording how long each request took to handle, by route and status. Routes are
+/// labeled with their pattern, so series IDs in paths don't each become a time series.
+pub async fn record_latency(request: Request<Body>, next: Next<Body>) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let response = next.run(request).await;
+    metrics::histogram!(
+        "fred_proxy_http_request_duration_seconds",
+        started.elapsed(),
+        "method" => method,
+        "path" => path,
+        "status" => response.status().as_u16().to_string(),
+    );
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::{install, record_latency, render};
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_records_handler_latency_by_route() {
+        let handle = install().unwrap();
+        let app = Router::new()
+            .route("/v0/cache/series/:series_id", get(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn(record_latency));
+        let request = axum::http::Request::builder()
+            .uri("/v0/cache/series/SP500")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap();
+        let rendered = render(axum::extract::State(handle)).await;
+        assert!(
+            rendered.contains(
+                r#"fred_proxy_http_request_duration_seconds_count{method="GET",path="/v0/cache/series/:series_id",status="200"} 1"#
+            ),
+            "{rendered}"
+        );
+    }
+}
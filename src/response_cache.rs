@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use flate2::{write::GzEncoder, Compression};
+
+use crate::entities::ObservationSummary;
+
+/// Bounded in-memory cache of gzip-compressed response bodies, so the same large payload
+/// isn't compressed again on every request. Entries are keyed by series and the request's
+/// query string and evicted least-recently-used first.
+#[derive(Debug, Clone)]
+pub struct PrecompressedResponses {
+    capacity: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    bodies: HashMap<(String, String), Entry>,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    response: PrecompressedResponse,
+    last_used: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrecompressedResponse {
+    pub content_type: &'static str,
+    pub gzipped: Bytes,
+    /// Summary of the observations in the body, sent along as headers
+    pub summary: ObservationSummary,
+}
+
+impl PrecompressedResponses {
+    /// A capacity of 0 disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        PrecompressedResponses {
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn get(&self, series_id: &str, query: &str) -> Option<PrecompressedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        let entry = entries
+            .bodies
+            .get_mut(&(series_id.to_string(), query.to_string()))?;
+        entry.last_used = clock;
+        Some(entry.response.clone())
+    }
+
+    /// Compress `body` and remember it for `series_id` and `query`.
+    pub fn insert(
+        &self,
+        series_id: &str,
+        query: &str,
+        content_type: &'static str,
+        body: &[u8],
+        summary: ObservationSummary,
+    ) -> std::io::Result<PrecompressedResponse> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body)?;
+        let response = PrecompressedResponse {
+            content_type,
+            gzipped: Bytes::from(encoder.finish()?),
+            summary,
+        };
+        if !self.is_enabled() {
+            return Ok(response);
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        if entries.bodies.len() >= self.capacity {
+            let least_recently_used = entries
+                .bodies
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = least_recently_used {
+                entries.bodies.remove(&key);
+            }
+        }
+        entries.bodies.insert(
+            (series_id.to_string(), query.to_string()),
+            Entry {
+                response: response.clone(),
+                last_used: clock,
+            },
+        );
+        Ok(response)
+    }
+
+    /// Forget every response.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().bodies.clear();
+    }
+
+    /// Forget every response for `series_id`, e.g. after its cached observations change.
+    pub fn invalidate_series(&self, series_id: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .bodies
+            .retain(|(id, _), _| id != series_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PrecompressedResponses;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_evicts_least_recently_used_and_invalidates_series() {
+        let cache = PrecompressedResponses::new(2);
+        cache
+            .insert("SP500", "a", "application/json", b"[1]", Default::default())
+            .unwrap();
+        cache
+            .insert("GDP", "b", "application/json", b"[2]", Default::default())
+            .unwrap();
+        assert!(cache.get("SP500", "a").is_some());
+        cache
+            .insert("GDP", "c", "application/json", b"[3]", Default::default())
+            .unwrap();
+        assert!(cache.get("GDP", "b").is_none());
+        let mut body = String::new();
+        GzDecoder::new(&cache.get("SP500", "a").unwrap().gzipped[..])
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, "[1]");
+        cache.invalidate_series("SP500");
+        assert!(cache.get("SP500", "a").is_none());
+        assert!(cache.get("GDP", "c").is_some());
+    }
+}
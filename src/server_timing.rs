@@ -0,0 +1,100 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDate;
+
+/// Durations of the phases of handling one request, reported to clients in a
+/// [`Server-Timing`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Server-Timing)
+/// header. A phase that runs more than once (e.g. two cache reads) is reported as the sum.
+/// Also counts the pages fetched from FRED, which usually explain a slow `fred` phase, and the
+/// observations written to the local cache, and notes the dates of the observations from FRED.
+#[derive(Debug, Default)]
+pub struct ServerTiming {
+    phases: Mutex<Vec<(&'static str, Duration)>>,
+    fred_pages: AtomicUsize,
+    rows_written: AtomicUsize,
+    fred_ranges: Mutex<Vec<(NaiveDate, NaiveDate)>>,
+}
+
+impl ServerTiming {
+    pub fn record(&self, phase: &'static str, duration: Duration) {
+        let mut phases = self.phases.lock().unwrap();
+        match phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += duration,
+            None => phases.push((phase, duration)),
+        }
+    }
+
+    /// Run `future` to completion, recording how long it took as `phase`.
+    pub async fn time<F: Future>(&self, phase: &'static str, future: F) -> F::Output {
+        let start = Instant::now();
+        let output = future.await;
+        self.record(phase, start.elapsed());
+        output
+    }
+
+    /// Total time recorded for `phase`
+    pub fn duration(&self, phase: &str) -> Duration {
+        self.phases
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| *name == phase)
+            .map(|(_, duration)| *duration)
+            .sum()
+    }
+
+    pub fn add_fred_pages(&self, pages: usize) {
+        self.fred_pages.fetch_add(pages, Ordering::Relaxed);
+    }
+
+    pub fn fred_pages(&self) -> usize {
+        self.fred_pages.load(Ordering::Relaxed)
+    }
+
+    /// Note that FRED returned observations from `first` through `last`.
+    pub fn add_fred_range(&self, first: NaiveDate, last: NaiveDate) {
+        self.fred_ranges.lock().unwrap().push((first, last));
+    }
+
+    /// The date ranges of the observations returned by FRED, in the order fetched
+    pub fn fred_ranges(&self) -> Vec<(NaiveDate, NaiveDate)> {
+        self.fred_ranges.lock().unwrap().clone()
+    }
+
+    pub fn add_rows_written(&self, rows: usize) {
+        self.rows_written.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    pub fn rows_written(&self) -> usize {
+        self.rows_written.load(Ordering::Relaxed)
+    }
+
+    /// Header value listing each phase in the order first recorded, in milliseconds.
+    pub fn header_value(&self) -> String {
+        self.phases
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, duration)| format!("{};dur={:.1}", name, duration.as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ServerTiming;
+    use std::time::Duration;
+
+    #[test]
+    fn test_header_value_sums_repeated_phases() {
+        let timing = ServerTiming::default();
+        timing.record("cache_read", Duration::from_micros(1500));
+        timing.record("fred", Duration::from_millis(120));
+        timing.record("cache_read", Duration::from_micros(500));
+        assert_eq!(timing.header_value(), "cache_read;dur=2.0, fred;dur=120.0");
+    }
+}
@@ -0,0 +1,256 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::entities::{Aggregation, RealtimeObservation, ResamplePeriod, Transform};
+
+/// FRED's placeholder for an observation with no value
+const MISSING: &str = ".";
+
+/// Replace each value with its change from the previous numeric observation.
+///
+/// Missing values (`"."`) are skipped over rather than breaking the chain, and stay missing in
+/// the output, as does the first numeric observation (it has nothing to change from) and a
+/// percent change from zero.
+pub fn apply_transform(
+    observations: &[RealtimeObservation],
+    transform: Transform,
+) -> Vec<RealtimeObservation> {
+    let mut previous: Option<&str> = None;
+    observations
+        .iter()
+        .map(|observation| {
+            let value = match observation.numeric_value() {
+                Some(current) => {
+                    let changed = previous.and_then(|previous| {
+                        change(previous, &observation.value, current, transform)
+                    });
+                    previous = Some(&observation.value);
+                    changed.unwrap_or_else(|| MISSING.to_string())
+                }
+                None => MISSING.to_string(),
+            };
+            RealtimeObservation {
+                date: observation.date,
+                value,
+                realtime_start: observation.realtime_start,
+                realtime_end: observation.realtime_end,
+                value_real: None,
+            }
+        })
+        .collect()
+}
+
+/// Index each value to 100 at the value of the observation on `base_date`, or at the first
+/// numeric value if `None`, e.g. to compare the growth of several series on one chart. Missing
+/// values stay missing. `None` if there is no usable base value: it is missing or zero.
+pub fn rebase(
+    mut observations: Vec<RealtimeObservation>,
+    base_date: Option<NaiveDate>,
+) -> Option<Vec<RealtimeObservation>> {
+    let base = match base_date {
+        Some(base_date) => observations
+            .iter()
+            .find(|observation| observation.date == base_date)?
+            .numeric_value()?,
+        None => observations
+            .iter()
+            .find_map(|observation| observation.numeric_value())?,
+    };
+    if base == 0.0 {
+        return None;
+    }
+    for observation in &mut observations {
+        if let Some(value) = observation.numeric_value() {
+            observation.value = format!("{:.4}", value / base * 100.0);
+            observation.value_real = None;
+        }
+    }
+    Some(observations)
+}
+
+/// Round each value to `places` decimal places. Missing values stay missing.
+pub fn round_values(
+    mut observations: Vec<RealtimeObservation>,
+    places: usize,
+) -> Vec<RealtimeObservation> {
+    for observation in &mut observations {
+        if let Some(value) = observation.numeric_value() {
+            observation.value_real = None;
+            let rounded = format!("{value:.places$}");
+            // don't turn a small negative value into "-0.00"
+            observation.value = match rounded.strip_prefix('-') {
+                Some(unsigned) if rounded.parse::<f64>() == Ok(0.0) => unsigned.to_string(),
+                _ => rounded,
+            };
+        }
+    }
+    observations
+}
+
+fn change(
+    previous: &str,
+    current_text: &str,
+    current: f64,
+    transform: Transform,
+) -> Option<String> {
+    let previous_value = previous.parse::<f64>().ok()?;
+    match transform {
+        // keep the precision of the inputs, so that e.g. 0.3 - 0.1 reads 0.2
+        Transform::Delta => Some(format!(
+            "{:.*}",
+            decimal_places(previous).max(decimal_places(current_text)),
+            current - previous_value
+        )),
+        Transform::PctChange if previous_value != 0.0 => Some(format!(
+            "{:.4}",
+            (current - previous_value) / previous_value.abs() * 100.0
+        )),
+        Transform::PctChange => None,
+    }
+}
+
+/// Combine the observations in each month, quarter, or year into one, dated the first day of
+/// the period like FRED's own lower-frequency series.
+///
+/// Missing values are left out of the aggregate; a period with none but missing values stays
+/// missing. This is an approximation of FRED's aggregation (e.g. FRED may require a minimum
+/// number of observations per period), computed from whatever is cached.
+pub fn resample(
+    observations: &[RealtimeObservation],
+    period: ResamplePeriod,
+    aggregation: Aggregation,
+) -> Vec<RealtimeObservation> {
+    let mut resampled = Vec::<RealtimeObservation>::new();
+    let mut start = 0;
+    while start < observations.len() {
+        let period_start = period_start(observations[start].date, period);
+        let len = observations[start..]
+            .iter()
+            .take_while(|observation| self::period_start(observation.date, period) == period_start)
+            .count();
+        resampled.push(RealtimeObservation {
+            date: period_start,
+            value: aggregate(&observations[start..start + len], aggregation)
+                .unwrap_or_else(|| MISSING.to_string()),
+            ..Default::default()
+        });
+        start += len;
+    }
+    resampled
+}
+
+fn period_start(date: NaiveDate, period: ResamplePeriod) -> NaiveDate {
+    let month = match period {
+        ResamplePeriod::Monthly => date.month(),
+        ResamplePeriod::Quarterly => (date.month() - 1) / 3 * 3 + 1,
+        ResamplePeriod::Annual => 1,
+    };
+    NaiveDate::from_ymd_opt(date.year(), month, 1).unwrap()
+}
+
+fn aggregate(observations: &[RealtimeObservation], aggregation: Aggregation) -> Option<String> {
+    let numeric: Vec<(&str, f64)> = observations
+        .iter()
+        .filter_map(|o| Some((o.value.as_str(), o.numeric_value()?)))
+        .collect();
+    let places = numeric.iter().map(|(text, _)| decimal_places(text)).max()?;
+    let sum: f64 = numeric.iter().map(|(_, value)| value).sum();
+    match aggregation {
+        Aggregation::Last => numeric.last().map(|(text, _)| text.to_string()),
+        Aggregation::Sum => Some(format!("{sum:.places$}")),
+        // an average deserves a little more precision than its inputs
+        Aggregation::Avg => Some(format!("{:.*}", places + 2, sum / numeric.len() as f64)),
+    }
+}
+
+fn decimal_places(value: &str) -> usize {
+    value
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_transform, rebase, resample, round_values};
+    use crate::entities::{Aggregation, RealtimeObservation, ResamplePeriod, Transform};
+
+    fn observations(values: &[&str]) -> Vec<RealtimeObservation> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| RealtimeObservation {
+                date: chrono::NaiveDate::from_ymd_opt(2023, 1, 1 + i as u32).unwrap(),
+                value: value.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn values(observations: &[RealtimeObservation]) -> Vec<&str> {
+        observations.iter().map(|o| o.value.as_str()).collect()
+    }
+
+    #[test]
+    fn test_delta_skips_missing_values() {
+        let transformed = apply_transform(
+            &observations(&["0.1", "0.3", ".", "1", "0"]),
+            Transform::Delta,
+        );
+        assert_eq!(values(&transformed), vec![".", "0.2", ".", "0.7", "-1"]);
+    }
+
+    #[test]
+    fn test_pct_change() {
+        let transformed = apply_transform(
+            &observations(&["100", "110", "0", "5"]),
+            Transform::PctChange,
+        );
+        assert_eq!(values(&transformed), vec![".", "10.0000", "-100.0000", "."]);
+    }
+
+    #[test]
+    fn test_resample() {
+        let observations: Vec<RealtimeObservation> = [
+            ("2023-01-30", "1.5"),
+            ("2023-01-31", "."),
+            ("2023-02-01", "2"),
+            ("2023-02-02", "4"),
+            ("2023-04-03", "."),
+        ]
+        .iter()
+        .map(|(date, value)| RealtimeObservation {
+            date: date.parse().unwrap(),
+            value: value.to_string(),
+            ..Default::default()
+        })
+        .collect();
+        let monthly = resample(&observations, ResamplePeriod::Monthly, Aggregation::Avg);
+        let dates: Vec<String> = monthly.iter().map(|o| o.date.to_string()).collect();
+        assert_eq!(dates, vec!["2023-01-01", "2023-02-01", "2023-04-01"]);
+        assert_eq!(values(&monthly), vec!["1.500", "3.00", "."]);
+        let quarterly = resample(&observations, ResamplePeriod::Quarterly, Aggregation::Sum);
+        assert_eq!(values(&quarterly), vec!["7.5", "."]);
+        let annual = resample(&observations, ResamplePeriod::Annual, Aggregation::Last);
+        assert_eq!(values(&annual), vec!["4"]);
+    }
+
+    #[test]
+    fn test_round_values() {
+        let rounded = round_values(observations(&["3970.1549", ".", "-0.001", "12"]), 2);
+        assert_eq!(values(&rounded), vec!["3970.15", ".", "0.00", "12.00"]);
+        let rounded = round_values(observations(&["3970.5"]), 0);
+        assert_eq!(values(&rounded), vec!["3970"]);
+    }
+
+    #[test]
+    fn test_rebase() {
+        let rebased = rebase(observations(&[".", "50", "75", "."]), None).unwrap();
+        assert_eq!(values(&rebased), vec![".", "100.0000", "150.0000", "."]);
+        let base_date = "2023-01-03".parse().ok();
+        let rebased = rebase(observations(&[".", "50", "75"]), base_date).unwrap();
+        assert_eq!(values(&rebased), vec![".", "66.6667", "100.0000"]);
+        // a missing or zero base can't be indexed to
+        let base_date = "2023-01-01".parse().ok();
+        assert!(rebase(observations(&[".", "50"]), base_date).is_none());
+        assert!(rebase(observations(&["0", "50"]), None).is_none());
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper::StatusCode;
+use tokio::sync::Mutex;
+
+use stlouisfed_fred_web_proxy::{
+    entities::UpstreamStatus,
+    fred::{request_series_from_fred, FredClient},
+};
+
+/// Series asked about to see whether FRED answers. Any would do; this one is FRED's own example.
+const PROBE_SERIES_ID: &str = "GNPCA";
+
+/// The last check of whether FRED is reachable, reused for a while so that polling the status
+/// doesn't spend FRED quota.
+#[derive(Debug, Clone)]
+pub struct UpstreamStatusCache {
+    ttl: Duration,
+    last: Arc<Mutex<Option<(Instant, UpstreamStatus)>>>,
+}
+
+impl UpstreamStatusCache {
+    pub fn new(ttl: Duration) -> Self {
+        UpstreamStatusCache {
+            ttl,
+            last: Default::default(),
+        }
+    }
+
+    /// FRED's status as of the last check, checking again if that is older than the TTL.
+    /// Concurrent callers wait for the same check.
+    pub async fn get(&self, fred: &FredClient) -> UpstreamStatus {
+        let mut last = self.last.lock().await;
+        if let Some((checked, status)) = last.as_ref() {
+            if checked.elapsed() < self.ttl {
+                return status.clone();
+            }
+        }
+        let status = check(fred).await;
+        *last = Some((Instant::now(), status.clone()));
+        status
+    }
+}
+
+/// Ask FRED about a series, and time the answer. An error FRED answers itself (e.g. a rejected
+/// API key) still shows it is reachable; only failing to get an answer at all doesn't.
+async fn check(fred: &FredClient) -> UpstreamStatus {
+    let started = Instant::now();
+    let result = request_series_from_fred(fred, PROBE_SERIES_ID).await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let error = result.err().filter(|e| {
+        e.status_code.is_server_error() || e.status_code == StatusCode::TOO_MANY_REQUESTS
+    });
+    UpstreamStatus {
+        reachable: error.is_none(),
+        latency_ms,
+        checked_at: chrono::Utc::now(),
+        error: error.map(|e| e.to_string()),
+    }
+}
@@ -0,0 +1,117 @@
+use crate::entities::{
+    FredEconomicDataSeries, ObservationWarning, RealtimeObservation, WarningKind,
+};
+
+/// Observations further than this many standard deviations from the mean are flagged.
+const OUTLIER_Z_SCORE: f64 = 4.0;
+
+/// Fewer observations than this say too little about a series' spread to call any an outlier.
+const MIN_OBSERVATIONS_FOR_OUTLIERS: usize = 10;
+
+/// Flag observations that look like data glitches: negative values in a series that should never
+/// go negative (judged from its units and title), and extreme outliers by z-score.
+///
+/// This is only a heuristic; it never changes the observations.
+pub fn validate_observations(
+    series: &FredEconomicDataSeries,
+    observations: &[RealtimeObservation],
+) -> Vec<ObservationWarning> {
+    let numeric: Vec<(&RealtimeObservation, f64)> = observations
+        .iter()
+        .filter_map(|o| Some((o, o.numeric_value()?)))
+        .collect();
+    let non_negative = expects_non_negative(series);
+    let spread = (numeric.len() >= MIN_OBSERVATIONS_FOR_OUTLIERS)
+        .then(|| mean_and_std_dev(numeric.iter().map(|(_, value)| *value)))
+        .filter(|(_, std_dev)| *std_dev > 0.0);
+    let mut warnings = Vec::new();
+    for (observation, value) in numeric {
+        if non_negative && value < 0.0 {
+            warnings.push(ObservationWarning {
+                date: observation.date,
+                value: observation.value.clone(),
+                kind: WarningKind::Negative,
+                message: format!("negative value in a series measured in {}", series.units),
+            });
+        }
+        if let Some((mean, std_dev)) = spread {
+            let z_score = (value - mean) / std_dev;
+            if z_score.abs() > OUTLIER_Z_SCORE {
+                warnings.push(ObservationWarning {
+                    date: observation.date,
+                    value: observation.value.clone(),
+                    kind: WarningKind::Outlier,
+                    message: format!("{z_score:.1} standard deviations from the mean"),
+                });
+            }
+        }
+    }
+    warnings
+}
+
+/// Whether the series looks like a level that can't go negative, such as a price index, rather
+/// than a change, rate, or balance that can.
+fn expects_non_negative(series: &FredEconomicDataSeries) -> bool {
+    let units = series.units.to_lowercase();
+    let title = series.title.to_lowercase();
+    let can_be_negative = ["change", "percent", "rate", "balance", "net ", "spread"]
+        .iter()
+        .any(|word| units.contains(word) || title.contains(word));
+    !can_be_negative && (units.contains("index") || title.contains("price"))
+}
+
+fn mean_and_std_dev(values: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let (count, sum) = values
+        .clone()
+        .fold((0.0, 0.0), |(count, sum), value| (count + 1.0, sum + value));
+    let mean = sum / count;
+    let variance = values.map(|value| (value - mean).powi(2)).sum::<f64>() / count;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod test {
+    use super::validate_observations;
+    use crate::entities::{FredEconomicDataSeries, RealtimeObservation, WarningKind};
+
+    fn series(title: &str, units: &str) -> FredEconomicDataSeries {
+        FredEconomicDataSeries {
+            title: title.to_string(),
+            units: units.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn observations(values: &[&str]) -> Vec<RealtimeObservation> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| RealtimeObservation {
+                date: chrono::NaiveDate::from_ymd_opt(2023, 1, 1 + i as u32).unwrap(),
+                value: value.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_negative_price_index_is_flagged() {
+        let price_index = series("Consumer Price Index", "Index 1982-1984=100");
+        let warnings = validate_observations(&price_index, &observations(&["300.1", ".", "-3"]));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::Negative);
+        assert_eq!(warnings[0].value, "-3");
+        let change = series("Consumer Price Index", "Percent Change from Year Ago");
+        assert!(validate_observations(&change, &observations(&["3.2", "-0.1"])).is_empty());
+    }
+
+    #[test]
+    fn test_outlier_is_flagged() {
+        let mut values = vec!["100"; 19];
+        values.push("10000");
+        let warnings = validate_observations(&series("S&P 500", "Index"), &observations(&values));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::Outlier);
+        assert_eq!(warnings[0].date.to_string(), "2023-01-20");
+    }
+}